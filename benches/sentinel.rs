@@ -0,0 +1,114 @@
+//! Compares the hot paths of [`RawList`] and [`SentinelList`].
+//!
+//! Run with `cargo bench --bench sentinel`.
+
+use std::{hint::black_box, mem::offset_of, pin::pin, time::Instant};
+
+use linked_list_r4l::{GetLinksOffset, Links, OffsetAdapter, RawList, SentinelList};
+
+const NODES: usize = 1024;
+const ROUNDS: usize = 1000;
+
+struct Node {
+    value: usize,
+    links: Links<Node>,
+}
+
+// SAFETY: `links` is a `Links<Node>` field of `Node`.
+unsafe impl GetLinksOffset for Node {
+    type EntryType = Node;
+    const LINKS_OFFSET: usize = offset_of!(Node, links);
+}
+
+fn nodes() -> Vec<Node> {
+    (0..NODES)
+        .map(|value| Node {
+            value,
+            links: Links::new(),
+        })
+        .collect()
+}
+
+fn report(name: &str, start: Instant) {
+    let ops = (NODES * ROUNDS * 2) as f64;
+    let ns = start.elapsed().as_nanos() as f64 / ops;
+    println!("{name:<40} {ns:>8.2} ns/op");
+}
+
+fn bench_raw_list(nodes: &[Node]) {
+    let mut list = RawList::<OffsetAdapter<Node>>::new();
+
+    let start = Instant::now();
+    for _ in 0..ROUNDS {
+        for n in nodes {
+            // SAFETY: The nodes outlive the list and are not moved.
+            unsafe { list.push_back(black_box(n)) };
+        }
+        // Remove every other node, which unlinks them from the middle of the list, then the
+        // remaining ones, which unlinks the head of the list each time.
+        for n in nodes
+            .iter()
+            .step_by(2)
+            .chain(nodes.iter().skip(1).step_by(2))
+        {
+            // SAFETY: The node is on this list.
+            unsafe { list.remove(black_box(n)) };
+        }
+    }
+    report("RawList push_back + remove", start);
+
+    let start = Instant::now();
+    for _ in 0..ROUNDS {
+        for n in nodes {
+            // SAFETY: The nodes outlive the list and are not moved.
+            unsafe { list.push_front(black_box(n)) };
+        }
+        while let Some(n) = list.pop_front() {
+            black_box(n);
+        }
+    }
+    report("RawList push_front + pop_front", start);
+}
+
+fn bench_sentinel_list(nodes: &[Node]) {
+    let mut list = pin!(SentinelList::<Node>::new());
+
+    let start = Instant::now();
+    for _ in 0..ROUNDS {
+        for n in nodes {
+            // SAFETY: The nodes outlive the list and are not moved.
+            unsafe { list.as_mut().push_back(black_box(n)) };
+        }
+        for n in nodes
+            .iter()
+            .step_by(2)
+            .chain(nodes.iter().skip(1).step_by(2))
+        {
+            // SAFETY: The node is on this list.
+            unsafe { list.as_mut().remove(black_box(n)) };
+        }
+    }
+    report("SentinelList push_back + remove", start);
+
+    let start = Instant::now();
+    for _ in 0..ROUNDS {
+        for n in nodes {
+            // SAFETY: The nodes outlive the list and are not moved.
+            unsafe { list.as_mut().push_front(black_box(n)) };
+        }
+        while let Some(n) = list.as_mut().pop_front() {
+            black_box(n);
+        }
+    }
+    report("SentinelList push_front + pop_front", start);
+}
+
+fn main() {
+    let nodes = nodes();
+    bench_raw_list(&nodes);
+    bench_sentinel_list(&nodes);
+    assert_eq!(
+        nodes.iter().map(|n| n.value).sum::<usize>(),
+        NODES * (NODES - 1) / 2
+    );
+}
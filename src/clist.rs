@@ -0,0 +1,422 @@
+// SPDX-License-Identifier: GPL-2.0
+
+//! C-compatible lists.
+//!
+//! Based on the layout of `struct list_head` in linux/include/linux/types.h, so lists can be
+//! shared with C code.
+
+use core::{iter, marker::PhantomData, ptr, ptr::NonNull};
+
+/// The links used to link an object on a C-compatible linked list.
+///
+/// This has the same layout as the C `struct list_head`. Lists are circular and start with a
+/// head that is itself a [`CListHead`] not embedded in any entry. An empty list is a head whose
+/// `next` and `prev` point to itself.
+///
+/// Containing entries are recovered with [`container_of!`](crate::container_of).
+#[repr(C)]
+#[derive(Debug)]
+pub struct CListHead {
+    /// The next element on the list.
+    pub next: *mut CListHead,
+    /// The previous element on the list.
+    pub prev: *mut CListHead,
+}
+
+impl CListHead {
+    /// Constructs a new [`CListHead`] with null links.
+    ///
+    /// It must be initialised with [`CListHead::init`] once it has reached its final location and
+    /// before it is used as a list head.
+    pub const fn new() -> Self {
+        Self {
+            next: ptr::null_mut(),
+            prev: ptr::null_mut(),
+        }
+    }
+
+    /// Initialises the list head pointed to by `this` as an empty list, like `INIT_LIST_HEAD`.
+    ///
+    /// # Safety
+    ///
+    /// Callers must ensure that `this` is valid for writes.
+    pub unsafe fn init(this: *mut Self) {
+        // SAFETY: `this` is valid for writes by the safety requirements.
+        unsafe {
+            (*this).next = this;
+            (*this).prev = this;
+        }
+    }
+
+    /// Returns whether the links point to themselves, that is, the list is empty or the entry
+    /// was removed with [`RawCList::remove`].
+    pub fn is_self_linked(&self) -> bool {
+        ptr::eq(self.next, self) && ptr::eq(self.prev, self)
+    }
+}
+
+impl Default for CListHead {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A view of a C-compatible linked list.
+///
+/// This does not own the list head or its entries. It is used to walk and modify lists that may
+/// have been created by C code.
+///
+/// # Invariants
+///
+/// `head` points to an initialised list head, and all the entries on the list are valid.
+pub struct RawCList<'a> {
+    head: NonNull<CListHead>,
+    _p: PhantomData<&'a mut CListHead>,
+}
+
+impl<'a> RawCList<'a> {
+    /// Creates a view of the list whose head is pointed to by `head`.
+    ///
+    /// # Safety
+    ///
+    /// Callers must ensure that `head` points to an initialised list head, that the head and all
+    /// entries on the list remain valid for `'a`, and that nothing else accesses the list for as
+    /// long as the returned value is in use.
+    pub unsafe fn from_raw(head: NonNull<CListHead>) -> Self {
+        // INVARIANT: Guaranteed by the safety requirements.
+        Self {
+            head,
+            _p: PhantomData,
+        }
+    }
+
+    /// Returns the raw pointer to the list head.
+    pub fn as_raw(&self) -> NonNull<CListHead> {
+        self.head
+    }
+
+    /// Returns whether the list is empty.
+    pub fn is_empty(&self) -> bool {
+        // SAFETY: The head is valid by the type invariants.
+        unsafe { ptr::eq(self.head.as_ref().next, self.head.as_ptr()) }
+    }
+
+    /// Inserts `new` between `prev` and `next`, like `__list_add`.
+    ///
+    /// # Safety
+    ///
+    /// `prev` and `next` must be consecutive links on the list, and `new` must be valid and not
+    /// on any list.
+    unsafe fn insert_between(
+        new: NonNull<CListHead>,
+        prev: NonNull<CListHead>,
+        next: NonNull<CListHead>,
+    ) {
+        // SAFETY: All pointers are valid by the safety requirements.
+        unsafe {
+            (*next.as_ptr()).prev = new.as_ptr();
+            (*new.as_ptr()).next = next.as_ptr();
+            (*new.as_ptr()).prev = prev.as_ptr();
+            (*prev.as_ptr()).next = new.as_ptr();
+        }
+    }
+
+    /// Adds the given entry to the end (back) of the list, like `list_add_tail`.
+    ///
+    /// # Safety
+    ///
+    /// Callers must ensure that `new` is valid, isn't on any list, and remains valid while it is
+    /// on the list.
+    pub unsafe fn push_back(&mut self, new: NonNull<CListHead>) {
+        // SAFETY: The head is valid by the type invariants, and `prev` of the head is the last
+        // entry.
+        unsafe {
+            let prev = NonNull::new_unchecked(self.head.as_ref().prev);
+            Self::insert_between(new, prev, self.head);
+        }
+    }
+
+    /// Adds the given entry to the first (front) of the list, like `list_add`.
+    ///
+    /// # Safety
+    ///
+    /// Callers must ensure that `new` is valid, isn't on any list, and remains valid while it is
+    /// on the list.
+    pub unsafe fn push_front(&mut self, new: NonNull<CListHead>) {
+        // SAFETY: The head is valid by the type invariants, and `next` of the head is the first
+        // entry.
+        unsafe { self.insert_after(self.head, new) };
+    }
+
+    /// Inserts the given entry after `existing`.
+    ///
+    /// # Safety
+    ///
+    /// Callers must ensure that `existing` is on this list (or is its head), and that `new` is
+    /// valid, isn't on any list, and remains valid while it is on the list.
+    pub unsafe fn insert_after(&mut self, existing: NonNull<CListHead>, new: NonNull<CListHead>) {
+        // SAFETY: `existing` is on the list, so its `next` is valid.
+        unsafe {
+            let next = NonNull::new_unchecked(existing.as_ref().next);
+            Self::insert_between(new, existing, next);
+        }
+    }
+
+    /// Removes the given entry from the list, like `list_del_init`.
+    ///
+    /// The links of the removed entry are left pointing to themselves.
+    ///
+    /// # Safety
+    ///
+    /// Callers must ensure that `entry` is on this list and is not its head.
+    pub unsafe fn remove(&mut self, entry: NonNull<CListHead>) {
+        // SAFETY: `entry` is on the list, so it and its neighbours are valid.
+        unsafe {
+            let next = entry.as_ref().next;
+            let prev = entry.as_ref().prev;
+            (*next).prev = prev;
+            (*prev).next = next;
+            CListHead::init(entry.as_ptr());
+        }
+    }
+
+    /// Removes the first entry of the list and returns it.
+    ///
+    /// Returns `None` if the list is empty.
+    pub fn pop_front(&mut self) -> Option<NonNull<CListHead>> {
+        let front = self.front()?;
+        // SAFETY: `front` was just taken from the list.
+        unsafe { self.remove(front) };
+        Some(front)
+    }
+
+    /// Returns the first entry of the list without removing it.
+    pub fn front(&self) -> Option<NonNull<CListHead>> {
+        if self.is_empty() {
+            return None;
+        }
+        // SAFETY: The head is valid by the type invariants, and the list is non-empty.
+        Some(unsafe { NonNull::new_unchecked(self.head.as_ref().next) })
+    }
+
+    /// Returns the last entry of the list without removing it.
+    pub fn back(&self) -> Option<NonNull<CListHead>> {
+        if self.is_empty() {
+            return None;
+        }
+        // SAFETY: The head is valid by the type invariants, and the list is non-empty.
+        Some(unsafe { NonNull::new_unchecked(self.head.as_ref().prev) })
+    }
+
+    /// Returns an iterator over the links of the entries on the list, starting at the first one.
+    pub fn iter(&self) -> CListIter<'_> {
+        // SAFETY: The head is valid by the type invariants.
+        let (next, prev) = unsafe { (self.head.as_ref().next, self.head.as_ref().prev) };
+        CListIter {
+            head: self.head.as_ptr(),
+            next,
+            prev,
+            _p: PhantomData,
+        }
+    }
+}
+
+/// An iterator over the links of the entries on a C-compatible list.
+pub struct CListIter<'a> {
+    head: *mut CListHead,
+    next: *mut CListHead,
+    prev: *mut CListHead,
+    _p: PhantomData<&'a CListHead>,
+}
+
+impl iter::Iterator for CListIter<'_> {
+    type Item = NonNull<CListHead>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if ptr::eq(self.next, self.head) {
+            return None;
+        }
+        let cur = self.next;
+        if ptr::eq(cur, self.prev) {
+            // Both ends met, nothing left to yield after this one.
+            self.next = self.head;
+            self.prev = self.head;
+        } else {
+            // SAFETY: `cur` is on the list, so it is valid.
+            self.next = unsafe { (*cur).next };
+        }
+        NonNull::new(cur)
+    }
+}
+
+impl iter::DoubleEndedIterator for CListIter<'_> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if ptr::eq(self.prev, self.head) {
+            return None;
+        }
+        let cur = self.prev;
+        if ptr::eq(cur, self.next) {
+            // Both ends met, nothing left to yield after this one.
+            self.next = self.head;
+            self.prev = self.head;
+        } else {
+            // SAFETY: `cur` is on the list, so it is valid.
+            self.prev = unsafe { (*cur).prev };
+        }
+        NonNull::new(cur)
+    }
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    extern crate alloc;
+    use alloc::vec::Vec;
+    use core::{
+        mem::{align_of, offset_of, size_of},
+        ptr::{self, NonNull},
+    };
+
+    use super::{CListHead, RawCList};
+
+    #[repr(C)]
+    struct Example {
+        value: u32,
+        list: CListHead,
+    }
+
+    impl Example {
+        fn new(value: u32) -> Self {
+            Self {
+                value,
+                list: CListHead::new(),
+            }
+        }
+    }
+
+    /// Mirrors the C `list_add_tail`, to build lists the way C code does.
+    ///
+    /// # Safety
+    ///
+    /// `head` must point to an initialised list, and `new` to links that are on no list.
+    unsafe fn c_list_add_tail(new: *mut CListHead, head: *mut CListHead) {
+        // SAFETY: `head` and its last entry are valid, and so is `new`, by the safety
+        // requirements.
+        unsafe {
+            let prev = (*head).prev;
+            (*head).prev = new;
+            (*new).next = head;
+            (*new).prev = prev;
+            (*prev).next = new;
+        }
+    }
+
+    fn values(list: &RawCList<'_>) -> Vec<u32> {
+        list.iter()
+            .map(|l| {
+                // SAFETY: All links on the lists of the tests are embedded in an `Example`, and
+                // derived from a pointer to it.
+                unsafe { (*crate::container_of!(l.as_ptr(), Example, list)).value }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_layout() {
+        assert_eq!(size_of::<CListHead>(), 2 * size_of::<*mut ()>());
+        assert_eq!(align_of::<CListHead>(), align_of::<*mut ()>());
+        assert_eq!(offset_of!(CListHead, next), 0);
+        assert_eq!(offset_of!(CListHead, prev), size_of::<*mut ()>());
+    }
+
+    #[test]
+    fn test_walk_c_list() {
+        let mut head = CListHead::new();
+        let mut nodes: Vec<Example> = (0..4).map(Example::new).collect();
+        let head_ptr = &raw mut head;
+        let base = nodes.as_mut_ptr();
+
+        // SAFETY: All pointers are valid and outlive the list.
+        unsafe {
+            CListHead::init(head_ptr);
+            for i in 0..nodes.len() {
+                c_list_add_tail(&raw mut (*base.add(i)).list, head_ptr);
+            }
+        }
+
+        // SAFETY: The head was initialised above and outlives `list`.
+        let list = unsafe { RawCList::from_raw(NonNull::new(head_ptr).unwrap()) };
+        assert_eq!(values(&list), [0, 1, 2, 3]);
+        let rev: Vec<_> = list
+            .iter()
+            .rev()
+            .map(|l| {
+                // SAFETY: The links on the list are embedded in the elements of `nodes`, and
+                // derived from a pointer to them.
+                unsafe { (*crate::container_of!(l.as_ptr(), Example, list)).value }
+            })
+            .collect();
+        assert_eq!(rev, [3, 2, 1, 0]);
+
+        let mut iter = list.iter();
+        assert!(iter.next().is_some());
+        assert!(iter.next_back().is_some());
+        assert!(iter.next().is_some());
+        assert!(iter.next_back().is_some());
+        assert!(iter.next().is_none());
+        assert!(iter.next_back().is_none());
+    }
+
+    #[test]
+    fn test_modify() {
+        let mut head = CListHead::new();
+        let mut nodes: Vec<Example> = (0..4).map(Example::new).collect();
+        let head_ptr = &raw mut head;
+        let base = nodes.as_mut_ptr();
+        let link = |i: usize| {
+            // SAFETY: `base` points to the elements of `nodes`, which the tests only index within
+            // bounds, and no reference is created.
+            unsafe { NonNull::new_unchecked(&raw mut (*base.add(i)).list) }
+        };
+
+        // SAFETY: The head is valid and outlives the list.
+        let mut list = unsafe {
+            CListHead::init(head_ptr);
+            RawCList::from_raw(NonNull::new(head_ptr).unwrap())
+        };
+        assert!(list.is_empty());
+
+        // SAFETY: The entries are valid, not on any list, and outlive the list.
+        unsafe {
+            list.push_back(link(1));
+            list.push_front(link(0));
+            list.push_back(link(3));
+            list.insert_after(link(1), link(2));
+        }
+        assert_eq!(values(&list), [0, 1, 2, 3]);
+        assert!(ptr::eq(list.front().unwrap().as_ptr(), link(0).as_ptr()));
+        assert!(ptr::eq(list.back().unwrap().as_ptr(), link(3).as_ptr()));
+
+        // SAFETY: The entry is on the list.
+        unsafe { list.remove(link(2)) };
+        // SAFETY: The entry is valid and no longer on the list.
+        assert!(unsafe { link(2).as_ref() }.is_self_linked());
+        assert_eq!(values(&list), [0, 1, 3]);
+
+        assert!(ptr::eq(
+            list.pop_front().unwrap().as_ptr(),
+            link(0).as_ptr()
+        ));
+        assert!(ptr::eq(
+            list.pop_front().unwrap().as_ptr(),
+            link(1).as_ptr()
+        ));
+        assert!(ptr::eq(
+            list.pop_front().unwrap().as_ptr(),
+            link(3).as_ptr()
+        ));
+        assert!(list.pop_front().is_none());
+        assert!(list.is_empty());
+        // SAFETY: The head is valid, and `list` isn't used anymore.
+        assert!(unsafe { head_ptr.as_ref() }.unwrap().is_self_linked());
+    }
+}
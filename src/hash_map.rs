@@ -0,0 +1,658 @@
+// SPDX-License-Identifier: GPL-2.0
+
+//! Intrusive hash maps.
+//!
+//! Hash tables in the style of the PID and inode hash tables of the kernel, whose buckets are
+//! singly linked lists with back-links, like `hlist` in C. The bucket array is provided by the
+//! caller, so no allocation is needed. Entries use their own [`HashLinks`], and the same wrappers
+//! as [`List`](crate::List).
+//!
+//! The buckets are [`HashBucket`]s rather than [`RawList`](crate::RawList)s: a bucket is a single
+//! pointer to its first entry, and each entry points back to the link that points to it. This
+//! halves the size of the bucket array, and lets an entry be removed without finding its bucket,
+//! whereas removing the head of a `RawList` requires the list, and so hashing the key.
+
+#[cfg(feature = "alloc")]
+use alloc::{boxed::Box, sync::Arc};
+use core::{
+    hash::{BuildHasher, Hash},
+    marker::PhantomData,
+    pin::Pin,
+    ptr::{self, NonNull},
+};
+
+use crate::{
+    linked_list::Wrapper,
+    sync::{loom_const_fn, AtomicBool, Ordering, UnsafeCell},
+};
+
+/// Extracts the key of the entries of an [`IntrusiveHashMap`].
+pub trait KeyOf<K: ?Sized> {
+    /// Returns the key of the entry.
+    ///
+    /// It must not change while the entry is in a map.
+    fn key_of(&self) -> &K;
+}
+
+/// A descriptor of hash map elements.
+///
+/// It describes the type of hash map elements and provides a function to determine how to get the
+/// links to be used in a hash map.
+pub trait GetHashLinks {
+    /// The type of the entries in the hash map.
+    type EntryType: ?Sized;
+
+    /// Returns the links to be used when linking an entry within a hash map.
+    fn get_links(data: &Self::EntryType) -> &HashLinks<Self::EntryType>;
+}
+
+/// A descriptor of wrapped hash map elements.
+pub trait GetHashLinksWrapped: GetHashLinks {
+    /// Specifies which wrapper (e.g., `Box` and `Arc`) wraps the hash map entries.
+    type Wrapped: Wrapper<Self::EntryType>;
+}
+
+#[cfg(feature = "alloc")]
+impl<T: ?Sized> GetHashLinksWrapped for Box<T>
+where
+    Box<T>: GetHashLinks,
+{
+    type Wrapped = Box<<Box<T> as GetHashLinks>::EntryType>;
+}
+
+#[cfg(feature = "alloc")]
+impl<T: GetHashLinks + ?Sized> GetHashLinks for Box<T> {
+    type EntryType = T::EntryType;
+
+    #[inline]
+    fn get_links(data: &Self::EntryType) -> &HashLinks<Self::EntryType> {
+        <T as GetHashLinks>::get_links(data)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T: ?Sized> GetHashLinksWrapped for Arc<T>
+where
+    Arc<T>: GetHashLinks,
+{
+    type Wrapped = Arc<<Arc<T> as GetHashLinks>::EntryType>;
+}
+
+#[cfg(feature = "alloc")]
+impl<T: GetHashLinks + ?Sized> GetHashLinks for Arc<T> {
+    type EntryType = T::EntryType;
+
+    #[inline]
+    fn get_links(data: &Self::EntryType) -> &HashLinks<Self::EntryType> {
+        <T as GetHashLinks>::get_links(data)
+    }
+}
+
+impl<T: GetHashLinks + ?Sized> GetHashLinks for &T {
+    type EntryType = T::EntryType;
+
+    #[inline]
+    fn get_links(data: &Self::EntryType) -> &HashLinks<Self::EntryType> {
+        <T as GetHashLinks>::get_links(data)
+    }
+}
+
+impl<'a, T: ?Sized> GetHashLinksWrapped for &'a T
+where
+    &'a T: GetHashLinks,
+{
+    type Wrapped = &'a <&'a T as GetHashLinks>::EntryType;
+}
+
+impl<P: GetHashLinks> GetHashLinks for Pin<P> {
+    type EntryType = P::EntryType;
+
+    #[inline]
+    fn get_links(data: &Self::EntryType) -> &HashLinks<Self::EntryType> {
+        <P as GetHashLinks>::get_links(data)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T: ?Sized> GetHashLinksWrapped for Pin<Box<T>>
+where
+    Box<T>: GetHashLinks,
+{
+    type Wrapped = Pin<Box<<Box<T> as GetHashLinks>::EntryType>>;
+}
+
+#[cfg(feature = "alloc")]
+impl<T: ?Sized> GetHashLinksWrapped for Pin<Arc<T>>
+where
+    Arc<T>: GetHashLinks,
+{
+    type Wrapped = Pin<Arc<<Arc<T> as GetHashLinks>::EntryType>>;
+}
+
+impl<'a, T: ?Sized> GetHashLinksWrapped for Pin<&'a T>
+where
+    &'a T: GetHashLinks,
+{
+    type Wrapped = Pin<&'a <&'a T as GetHashLinks>::EntryType>;
+}
+
+type Link<T> = Option<NonNull<T>>;
+
+/// The links used to link an object in a hash map.
+///
+/// Instances of this type are usually embedded in structures and returned in calls to
+/// [`GetHashLinks::get_links`].
+pub struct HashLinks<T: ?Sized> {
+    inserted: AtomicBool,
+    node: UnsafeCell<HashNode<T>>,
+}
+
+// SAFETY: `HashLinks` can be safely sent to other threads but we restrict it to being `Send` only
+// when the hash map entries it points to are also `Send`.
+unsafe impl<T: ?Sized> Send for HashLinks<T> {}
+
+// SAFETY: `HashLinks` is usable from other threads via references but we restrict it to being
+// `Sync` only when the hash map entries it points to are also `Sync`.
+unsafe impl<T: ?Sized> Sync for HashLinks<T> {}
+
+impl<T: ?Sized> HashLinks<T> {
+    loom_const_fn! {
+        /// Constructs a new [`HashLinks`] instance that isn't inserted in any hash maps yet.
+        pub fn new() -> Self {
+            Self {
+                inserted: AtomicBool::new(false),
+                node: UnsafeCell::new(HashNode {
+                    next: None,
+                    pprev: None,
+                }),
+            }
+        }
+    }
+
+    fn acquire_for_insertion(&self) -> bool {
+        self.inserted
+            .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_ok()
+    }
+
+    fn release_after_removal(&self) {
+        self.inserted.store(false, Ordering::Release);
+    }
+}
+
+impl<T: ?Sized> Default for HashLinks<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The position of an entry in its bucket.
+///
+/// `pprev` points to the link that points to the entry: the `next` link of the previous entry, or
+/// the `first` link of the bucket for the first entry. So an entry can be unlinked without knowing
+/// its bucket. Both are `None` for entries that aren't in a bucket.
+struct HashNode<T: ?Sized> {
+    next: Link<T>,
+    pprev: Option<NonNull<Link<T>>>,
+}
+
+/// A bucket of an [`IntrusiveHashMap`].
+pub struct HashBucket<G: GetHashLinks> {
+    first: Link<G::EntryType>,
+}
+
+impl<G: GetHashLinks> HashBucket<G> {
+    /// Constructs a new empty bucket.
+    pub const fn new() -> Self {
+        Self { first: None }
+    }
+
+    /// Returns whether the bucket is empty.
+    pub const fn is_empty(&self) -> bool {
+        self.first.is_none()
+    }
+
+    /// Links the given entry at the front of the given bucket.
+    ///
+    /// # Safety
+    ///
+    /// Callers must ensure that `bucket` is valid and in the array of a map, and that the links of
+    /// `ptr` are acquired by that map and not linked in any bucket.
+    unsafe fn link(bucket: NonNull<Self>, ptr: NonNull<G::EntryType>) {
+        // SAFETY: The bucket and the entry are valid by the safety requirements, and the links of
+        // the entries in the bucket are owned by the map.
+        unsafe {
+            let first = ptr::addr_of_mut!((*bucket.as_ptr()).first);
+            let node = G::get_links(ptr.as_ref()).node.get();
+            if let Some(next) = *first {
+                (*G::get_links(next.as_ref()).node.get()).pprev =
+                    Some(NonNull::new_unchecked(ptr::addr_of_mut!((*node).next)));
+            }
+            (*node).next = *first;
+            (*node).pprev = Some(NonNull::new_unchecked(first));
+            *first = Some(ptr);
+        }
+    }
+
+    /// Unlinks the given entry from its bucket and returns the pointer it was linked by.
+    ///
+    /// Returns `None` if the entry isn't in a bucket. Its links stay acquired.
+    ///
+    /// # Safety
+    ///
+    /// Callers must ensure that `entry` is either in the map that is using it or in no hash map.
+    unsafe fn unlink(entry: &G::EntryType) -> Option<NonNull<G::EntryType>> {
+        let node = G::get_links(entry).node.get();
+        // SAFETY: The entry is in the map or in no hash map by the safety requirements, so its
+        // links are owned by the map or not used by anyone.
+        let pprev = unsafe { (*node).pprev }?;
+        // SAFETY: The links of the entries in the map and its buckets are owned by the map, and
+        // `pprev` points to the link that points to the entry.
+        unsafe {
+            let ptr = (*pprev.as_ptr()).unwrap();
+            let next = (*node).next;
+            *pprev.as_ptr() = next;
+            if let Some(next) = next {
+                (*G::get_links(next.as_ref()).node.get()).pprev = Some(pprev);
+            }
+            (*node).next = None;
+            (*node).pprev = None;
+            Some(ptr)
+        }
+    }
+}
+
+impl<G: GetHashLinks> Default for HashBucket<G> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// SAFETY: Buckets only point to entries while they are in the array of a map, which borrows them.
+// We restrict them to being `Send` only when their entries are also `Send`.
+unsafe impl<G: GetHashLinks> Send for HashBucket<G> where G::EntryType: Send {}
+
+// SAFETY: Buckets only point to entries while they are in the array of a map, which borrows them.
+// We restrict them to being `Sync` only when their entries are also `Sync`.
+unsafe impl<G: GetHashLinks> Sync for HashBucket<G> where G::EntryType: Sync {}
+
+/// The number of old buckets moved to the new bucket array by each insertion or removal while a
+/// resize is in progress.
+const MIGRATE_PER_OP: usize = 1;
+
+/// A hash map of wrapped entries, chained through their [`HashLinks`].
+///
+/// Entries are found by the key returned by [`KeyOf`], and several entries may have the same key.
+/// Ownership of the entries is transferred to the map while they are in it, like with
+/// [`List`](crate::List). Insertion and removal take constant time, and removal doesn't need the
+/// key: entries are unlinked through their own links.
+///
+/// Resizing is incremental: after [`IntrusiveHashMap::start_resize`], each insertion and removal
+/// moves the entries of one old bucket to the new bucket array, and lookups check both arrays. The
+/// old array is handed back by [`IntrusiveHashMap::migrate`] once it is empty.
+///
+/// # Examples
+///
+/// ```
+/// use linked_list_r4l::{GetHashLinks, HashBucket, HashLinks, IntrusiveHashMap, KeyOf};
+/// use std::collections::hash_map::RandomState;
+///
+/// struct Task {
+///     pid: u32,
+///     links: HashLinks<Task>,
+/// }
+///
+/// impl GetHashLinks for Task {
+///     type EntryType = Self;
+///     fn get_links(t: &Self) -> &HashLinks<Self> {
+///         &t.links
+///     }
+/// }
+///
+/// impl KeyOf<u32> for Task {
+///     fn key_of(&self) -> &u32 {
+///         &self.pid
+///     }
+/// }
+///
+/// let task = Task { pid: 1, links: HashLinks::new() };
+/// let mut buckets: [HashBucket<&Task>; 16] = Default::default();
+/// let mut pids = IntrusiveHashMap::new(&mut buckets, RandomState::new());
+/// pids.insert(&task);
+/// assert_eq!(pids.get(&1).map(|t| t.pid), Some(1));
+/// assert!(pids.get(&2).is_none());
+/// ```
+///
+/// # Invariants
+///
+/// `buckets` and the array in `old` are borrowed for `'a` and only accessed through these
+/// pointers, so that the `pprev` links of the entries stay valid. The entries in the buckets are
+/// chained through the nodes in their [`HashLinks`], and their links are owned by the map.
+pub struct IntrusiveHashMap<'a, G: GetHashLinksWrapped, K: ?Sized, H> {
+    buckets: NonNull<[HashBucket<G>]>,
+    /// The buckets being migrated away from during a resize, and how many of them are empty.
+    old: Option<(NonNull<[HashBucket<G>]>, usize)>,
+    len: usize,
+    hasher: H,
+    _buckets: PhantomData<&'a mut [HashBucket<G>]>,
+    _key: PhantomData<fn(&K)>,
+}
+
+impl<'a, G, K, H> IntrusiveHashMap<'a, G, K, H>
+where
+    G: GetHashLinksWrapped,
+    G::EntryType: KeyOf<K>,
+    K: Hash + Eq + ?Sized,
+    H: BuildHasher,
+{
+    /// Constructs a new empty map that uses the given buckets.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there are no buckets, or if any of them isn't empty.
+    pub fn new(buckets: &'a mut [HashBucket<G>], hasher: H) -> Self {
+        assert!(!buckets.is_empty(), "a hash map needs at least one bucket");
+        assert!(
+            buckets.iter().all(HashBucket::is_empty),
+            "buckets must be empty"
+        );
+        Self {
+            buckets: NonNull::from(buckets),
+            old: None,
+            len: 0,
+            hasher,
+            _buckets: PhantomData,
+            _key: PhantomData,
+        }
+    }
+
+    /// Returns the number of entries in the map.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns whether the map is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns whether a resize is in progress.
+    pub fn is_resizing(&self) -> bool {
+        self.old.is_some()
+    }
+
+    /// Returns the bucket of `key` in the given array.
+    fn bucket(&self, buckets: NonNull<[HashBucket<G>]>, key: &K) -> NonNull<HashBucket<G>> {
+        let idx = (self.hasher.hash_one(key) % buckets.len() as u64) as usize;
+        // SAFETY: The index is less than the length of the array.
+        unsafe { buckets.cast::<HashBucket<G>>().add(idx) }
+    }
+
+    /// Returns an entry of the bucket with the given key, if there is one.
+    ///
+    /// # Safety
+    ///
+    /// Callers must ensure that `bucket` is in one of the arrays of the map.
+    unsafe fn find(&self, bucket: NonNull<HashBucket<G>>, key: &K) -> Option<&G::EntryType> {
+        // SAFETY: The bucket is valid by the safety requirements.
+        let mut cur = unsafe { (*bucket.as_ptr()).first };
+        while let Some(ptr) = cur {
+            // SAFETY: Entries in the map are valid until they are removed, which needs `&mut self`.
+            let entry = unsafe { ptr.as_ref() };
+            if entry.key_of() == key {
+                return Some(entry);
+            }
+            // SAFETY: The entry is in the map, so its links are owned by the map.
+            cur = unsafe { (*G::get_links(entry).node.get()).next };
+        }
+        None
+    }
+
+    /// Adds the given object to the map.
+    ///
+    /// It is dropped if it's already in this (or another) hash map; this can happen for
+    /// reference-counted objects, so dropping means decrementing the reference count.
+    pub fn insert(&mut self, data: G::Wrapped) {
+        self.migrate_step(MIGRATE_PER_OP);
+        let ptr = data.into_pointer();
+        // SAFETY: We took ownership of the entry, so it is valid.
+        let entry = unsafe { ptr.as_ref() };
+        if !G::get_links(entry).acquire_for_insertion() {
+            // If insertion failed, rebuild object so that it can be freed.
+            // SAFETY: We just called `into_pointer` above.
+            unsafe { G::Wrapped::from_pointer(ptr) };
+            return;
+        }
+        let bucket = self.bucket(self.buckets, entry.key_of());
+        // SAFETY: The bucket is in the array of the map, and we just acquired the links.
+        unsafe { HashBucket::link(bucket, ptr) };
+        self.len += 1;
+    }
+
+    /// Returns an entry with the given key, if there is one.
+    pub fn get(&self, key: &K) -> Option<&G::EntryType> {
+        // SAFETY: The buckets are in the arrays of the map.
+        unsafe { self.find(self.bucket(self.buckets, key), key) }.or_else(|| {
+            let (old, _) = self.old?;
+            // SAFETY: The buckets are in the arrays of the map.
+            unsafe { self.find(self.bucket(old, key), key) }
+        })
+    }
+
+    /// Removes the given entry.
+    ///
+    /// The entry is unlinked through its links in constant time, without hashing its key or
+    /// searching its bucket.
+    ///
+    /// # Safety
+    ///
+    /// Callers must ensure that `data` is either in this map or in no hash map. It being in
+    /// another hash map leads to memory unsafety.
+    pub unsafe fn remove(&mut self, data: &G::Wrapped) -> Option<G::Wrapped> {
+        let entry = Wrapper::as_ref(data);
+        // SAFETY: The safety requirements are the same as `HashBucket::unlink`.
+        let ptr = unsafe { HashBucket::<G>::unlink(entry) }?;
+        G::get_links(entry).release_after_removal();
+        self.len -= 1;
+        self.migrate_step(MIGRATE_PER_OP);
+        // SAFETY: Elements in the map were inserted after a call to `into_pointer`.
+        Some(unsafe { G::Wrapped::from_pointer(ptr) })
+    }
+
+    /// Starts resizing the map to use the given buckets.
+    ///
+    /// Returns the buckets back if a resize is already in progress, or if there are no buckets or
+    /// any of them isn't empty.
+    pub fn start_resize(
+        &mut self,
+        buckets: &'a mut [HashBucket<G>],
+    ) -> Result<(), &'a mut [HashBucket<G>]> {
+        if self.old.is_some() || buckets.is_empty() || !buckets.iter().all(HashBucket::is_empty) {
+            return Err(buckets);
+        }
+        let old = core::mem::replace(&mut self.buckets, NonNull::from(buckets));
+        self.old = Some((old, 0));
+        Ok(())
+    }
+
+    /// Moves the entries of up to `max_buckets` old buckets to the new bucket array.
+    ///
+    /// Once all of them have been moved, this ends the resize and returns the old buckets, which
+    /// are all empty. Insertions and removals move entries too, so `migrate(0)` may return them.
+    pub fn migrate(&mut self, max_buckets: usize) -> Option<&'a mut [HashBucket<G>]> {
+        self.migrate_step(max_buckets);
+        match self.old {
+            Some((old, migrated)) if migrated == old.len() => {
+                self.old = None;
+                // SAFETY: The array was borrowed for `'a`, and the map doesn't use it anymore. Its
+                // buckets are empty, so no entry points to it.
+                Some(unsafe { &mut *old.as_ptr() })
+            }
+            _ => None,
+        }
+    }
+
+    fn migrate_step(&mut self, max_buckets: usize) {
+        let Some((old, migrated)) = self.old else {
+            return;
+        };
+        let end = old.len().min(migrated.saturating_add(max_buckets));
+        for idx in migrated..end {
+            // SAFETY: The index is less than the length of the old array.
+            let bucket = unsafe { old.cast::<HashBucket<G>>().add(idx) };
+            // SAFETY: The bucket is in the old array of the map.
+            while let Some(first) = unsafe { (*bucket.as_ptr()).first } {
+                // SAFETY: The entry is in the bucket, so it is valid.
+                let entry = unsafe { first.as_ref() };
+                // The links stay acquired so that the entry can't be inserted elsewhere while it
+                // is moved.
+                // SAFETY: The entry is in this map.
+                let ptr = unsafe { HashBucket::<G>::unlink(entry) }.unwrap();
+                let new = self.bucket(self.buckets, entry.key_of());
+                // SAFETY: The links were acquired when the entry was inserted, and it was just
+                // unlinked from the old bucket.
+                unsafe { HashBucket::link(new, ptr) };
+            }
+        }
+        self.old = Some((old, end));
+    }
+}
+
+impl<G: GetHashLinksWrapped, K: ?Sized, H> Drop for IntrusiveHashMap<'_, G, K, H> {
+    fn drop(&mut self) {
+        for buckets in [Some(self.buckets), self.old.map(|(old, _)| old)]
+            .into_iter()
+            .flatten()
+        {
+            for idx in 0..buckets.len() {
+                // SAFETY: The index is less than the length of the array.
+                let bucket = unsafe { buckets.cast::<HashBucket<G>>().add(idx).as_ptr() };
+                // SAFETY: The bucket is in an array of the map.
+                while let Some(ptr) = unsafe { (*bucket).first } {
+                    // SAFETY: The entry is in the bucket, so it is valid.
+                    let links = G::get_links(unsafe { ptr.as_ref() });
+                    // SAFETY: The entry is in this map.
+                    unsafe { HashBucket::<G>::unlink(ptr.as_ref()) };
+                    links.release_after_removal();
+                    // SAFETY: Elements in the map were inserted after a call to `into_pointer`.
+                    unsafe { G::Wrapped::from_pointer(ptr) };
+                }
+            }
+        }
+    }
+}
+
+// SAFETY: The map owns its wrapped entries and its hasher, and borrows its buckets exclusively, so
+// it can be sent to other threads when they can.
+unsafe impl<G: GetHashLinksWrapped, K: ?Sized, H: Send> Send for IntrusiveHashMap<'_, G, K, H> where
+    G::Wrapped: Send
+{
+}
+
+// SAFETY: Shared references to the map only hand out shared references to its entries and hasher.
+unsafe impl<G: GetHashLinksWrapped, K: ?Sized, H: Sync> Sync for IntrusiveHashMap<'_, G, K, H> where
+    G::Wrapped: Sync
+{
+}
+
+#[cfg(all(test, feature = "alloc", not(loom)))]
+mod tests {
+    use std::{collections::hash_map::RandomState, sync::Arc, vec::Vec};
+
+    use super::{GetHashLinks, HashBucket, HashLinks, IntrusiveHashMap, KeyOf};
+
+    struct Task {
+        pid: u32,
+        links: HashLinks<Self>,
+    }
+
+    impl GetHashLinks for Task {
+        type EntryType = Self;
+        fn get_links(obj: &Self) -> &HashLinks<Self> {
+            &obj.links
+        }
+    }
+
+    impl KeyOf<u32> for Task {
+        fn key_of(&self) -> &u32 {
+            &self.pid
+        }
+    }
+
+    fn new_task(pid: u32) -> Arc<Task> {
+        Arc::new(Task {
+            pid,
+            links: HashLinks::new(),
+        })
+    }
+
+    fn new_buckets(n: usize) -> Vec<HashBucket<Arc<Task>>> {
+        (0..n).map(|_| HashBucket::new()).collect()
+    }
+
+    #[test]
+    fn test_insert_get_remove() {
+        let tasks: Vec<_> = (0..32).map(new_task).collect();
+        let mut buckets = new_buckets(4);
+        let mut map = IntrusiveHashMap::new(&mut buckets, RandomState::new());
+        for t in &tasks {
+            map.insert(t.clone());
+        }
+        // Already in the map, so this is dropped.
+        map.insert(tasks[0].clone());
+        assert_eq!(map.len(), 32);
+        assert_eq!(Arc::strong_count(&tasks[0]), 2);
+        assert!(tasks.iter().all(|t| map.get(&t.pid).is_some()));
+        assert!(map.get(&32).is_none());
+
+        // Remove every other task, which includes the heads of the buckets.
+        for t in tasks.iter().step_by(2) {
+            // SAFETY: The task is in this map.
+            assert!(unsafe { map.remove(t) }.is_some());
+            // SAFETY: The task is in no list.
+            assert!(unsafe { map.remove(t) }.is_none());
+        }
+        assert_eq!(map.len(), 16);
+        for t in &tasks {
+            assert_eq!(map.get(&t.pid).is_some(), t.pid % 2 == 1);
+        }
+
+        drop(map);
+        assert!(buckets.iter().all(HashBucket::is_empty));
+        assert!(tasks.iter().all(|t| Arc::strong_count(t) == 1));
+    }
+
+    #[test]
+    fn test_incremental_resize() {
+        let tasks: Vec<_> = (0..64).map(new_task).collect();
+        let mut small = new_buckets(2);
+        let mut large = new_buckets(16);
+        let mut map = IntrusiveHashMap::new(&mut small, RandomState::new());
+        for t in &tasks[..48] {
+            map.insert(t.clone());
+        }
+
+        assert!(map.start_resize(&mut large).is_ok());
+        assert!(map.is_resizing());
+        assert!(map.start_resize(&mut []).is_err());
+
+        // Entries are found, inserted and removed while they are spread over both arrays.
+        assert!(tasks[..48].iter().all(|t| map.get(&t.pid).is_some()));
+        map.insert(tasks[48].clone());
+        // SAFETY: The task is in this map.
+        assert!(unsafe { map.remove(&tasks[0]) }.is_some());
+        for t in &tasks[49..] {
+            map.insert(t.clone());
+        }
+
+        let old = map.migrate(usize::MAX).unwrap();
+        assert_eq!(old.len(), 2);
+        assert!(old.iter().all(HashBucket::is_empty));
+        assert!(!map.is_resizing());
+        assert_eq!(map.len(), 63);
+        assert!(tasks[1..].iter().all(|t| map.get(&t.pid).is_some()));
+
+        drop(map);
+        assert!(tasks[1..].iter().all(|t| Arc::strong_count(t) == 1));
+    }
+}
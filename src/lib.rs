@@ -1,10 +1,109 @@
 #![cfg_attr(not(test), no_std)]
+#![cfg_attr(feature = "allocator_api", feature(allocator_api))]
 #![doc = include_str!("../README.md")]
 
+#[cfg(feature = "alloc")]
+extern crate alloc;
+#[cfg(feature = "std")]
+extern crate std;
+
+mod clist;
+mod hash_map;
 mod linked_list;
+mod llist;
+mod lru;
+mod mpsc;
+mod offset;
+mod pairing_heap;
+mod pool;
+mod priority;
 mod raw_list;
-pub use linked_list::List;
-pub use raw_list::{GetLinks, Links, RawList};
+mod raw_slist;
+mod rbtree;
+mod sentinel;
+mod slist;
+mod sync;
+mod sync_list;
+pub use clist::{CListHead, CListIter, RawCList};
+pub use hash_map::{
+    GetHashLinks, GetHashLinksWrapped, HashBucket, HashLinks, IntrusiveHashMap, KeyOf,
+};
+#[cfg(feature = "allocator_api")]
+pub use linked_list::InterchangeableAllocator;
+pub use linked_list::{GetLinksWrapped, List, RawHandle, Wrapper};
+pub use llist::LockFreeStack;
+#[cfg(feature = "alloc")]
+pub use lru::LruCache;
+pub use lru::LruList;
+pub use mpsc::{AtomicLinks, GetAtomicLinks, GetAtomicLinksWrapped, MpscQueue};
+pub use offset::{GetLinksOffset, OffsetAdapter};
+pub use pairing_heap::{GetHeapLinks, GetHeapLinksWrapped, HeapLinks, PairingHeap};
+pub use pool::{Pool, PoolBox};
+pub use priority::{PriorityLists, PriorityOf};
+pub use raw_list::{GetLinks, Links, ListCorruption, RawList};
+pub use raw_slist::{GetSLinks, RawSList, SLinks};
+pub use rbtree::{GetRbLinks, GetRbLinksWrapped, RBTree, RbLinks};
+pub use sentinel::{SentinelIter, SentinelList};
+pub use slist::{GetSLinksWrapped, SList};
+#[cfg(any(test, feature = "std"))]
+pub use sync_list::StdRawLock;
+pub use sync_list::{RawLock, RawSpinLock, StaticList, SyncList};
+
+#[cfg(feature = "serde")]
+#[doc(hidden)]
+pub use serde as __serde;
+
+/// Implements `Serialize` and `Deserialize` for a node defined by [`def_node!`] as its inner value.
+///
+/// Like [`__loom_const_fn!`], it is defined conditionally so that the `serde` feature is evaluated
+/// in this crate instead of the caller's.
+#[cfg(feature = "serde")]
+#[macro_export]
+#[doc(hidden)]
+macro_rules! __def_node_serde {
+    ($name:ident($type:ty)) => {
+        $crate::__def_node_serde!(@impl [] $name [$name] ($type));
+    };
+    ($name:ident<$gen:ident>($type:ty)) => {
+        $crate::__def_node_serde!(@impl [$gen] $name [$name<$gen>] ($type));
+    };
+    (@impl [$($gen:ident)?] $name:ident [$self:ty] ($type:ty)) => {
+        impl<$($gen)?> $crate::__serde::Serialize for $self
+        where
+            $type: $crate::__serde::Serialize,
+        {
+            fn serialize<S>(&self, serializer: S) -> ::core::result::Result<S::Ok, S::Error>
+            where
+                S: $crate::__serde::Serializer,
+            {
+                $crate::__serde::Serialize::serialize(&self.inner, serializer)
+            }
+        }
+
+        impl<'de, $($gen)?> $crate::__serde::Deserialize<'de> for $self
+        where
+            $type: $crate::__serde::Deserialize<'de>,
+        {
+            fn deserialize<D>(deserializer: D) -> ::core::result::Result<Self, D::Error>
+            where
+                D: $crate::__serde::Deserializer<'de>,
+            {
+                <$type as $crate::__serde::Deserialize<'de>>::deserialize(deserializer)
+                    .map($name::new)
+            }
+        }
+    };
+}
+
+/// Reports that nodes marked with `#[serde]` need the `serde` feature.
+#[cfg(not(feature = "serde"))]
+#[macro_export]
+#[doc(hidden)]
+macro_rules! __def_node_serde {
+    ($($t:tt)*) => {
+        ::core::compile_error!("`#[serde]` nodes require the `serde` feature of `linked_list_r4l`");
+    };
+}
 
 #[macro_export(local_inner_macros)]
 #[doc(hidden)]
@@ -26,11 +125,13 @@ macro_rules! __def_node_internal {
         }
 
         impl $name {
-            #[doc = "Create a node"]
-            pub const fn new(inner: $type) -> Self {
-                Self {
-                    inner,
-                    links: $crate::Links::new(),
+            $crate::__loom_const_fn! {
+                #[doc = "Create a node"]
+                pub fn new(inner: $type) -> Self {
+                    Self {
+                        inner,
+                        links: $crate::Links::new(),
+                    }
                 }
             }
 
@@ -74,11 +175,13 @@ macro_rules! __def_node_internal {
         }
 
         impl<$gen> $name<$gen> {
-            #[doc = "Create a node"]
-            pub const fn new(inner: $type) -> Self {
-                Self {
-                    inner,
-                    links: $crate::Links::new(),
+            $crate::__loom_const_fn! {
+                #[doc = "Create a node"]
+                pub fn new(inner: $type) -> Self {
+                    Self {
+                        inner,
+                        links: $crate::Links::new(),
+                    }
                 }
             }
 
@@ -133,12 +236,12 @@ macro_rules! __def_node_internal {
 ///     pub struct GenericNode<T>(T);
 /// }
 ///
-/// let node1 = Box::new(ExampleNode::new(0));
-/// let node2 = Box::new(ExampleNode::new(1));
-/// let mut list =  List::<Box<ExampleNode>>::new();
+/// let node1 = ExampleNode::new(0);
+/// let node2 = ExampleNode::new(1);
+/// let mut list =  List::<&ExampleNode>::new();
 ///
-/// list.push_back(node1);
-/// list.push_back(node2);
+/// list.push_back(&node1);
+/// list.push_back(&node2);
 ///
 /// for (i,e) in list.iter().enumerate() {
 ///     assert!(*e.inner() == i);
@@ -147,25 +250,70 @@ macro_rules! __def_node_internal {
 /// let node1 = list.pop_front().unwrap();
 /// let node2 = list.pop_front().unwrap();
 ///
-/// assert!(node1.into_inner() == 0);
-/// assert!(node2.into_inner() == 1);
+/// assert!(*node1.inner() == 0);
+/// assert!(*node2.inner() == 1);
 /// assert!(list.pop_front().is_none());
 ///
-/// let node1 = Box::new(GenericNode::new(0));
-/// let node2 = Box::new(GenericNode::new(1));
+/// let node1 = GenericNode::new(0);
+/// let node2 = GenericNode::new(1);
 ///
-/// let mut list =  List::<Box<GenericNode<usize>>>::new();
+/// let mut list =  List::<&GenericNode<usize>>::new();
 ///
-/// list.push_back(node1);
-/// list.push_back(node2);
+/// list.push_back(&node1);
+/// list.push_back(&node2);
 ///
 /// for (i,e) in list.iter().enumerate() {
 ///     assert!(*e.inner() == i);
 /// }
+///
+/// // Lists of nodes can be compared with their inner values.
+/// assert!(list == [0, 1]);
+/// ```
+///
+/// Nodes don't need an allocator: without the `alloc` feature, they can still be linked through
+/// references, e.g. to statics:
+///
+/// ```rust
+/// use linked_list_r4l::{def_node, List};
+///
+/// def_node! {
+///     struct StaticNode(usize);
+/// }
+///
+/// static NODES: [StaticNode; 2] = [StaticNode::new(0), StaticNode::new(1)];
+///
+/// let mut list = List::<&StaticNode>::new();
+/// for node in &NODES {
+///     list.push_back(node);
+/// }
+/// assert!(list == [0, 1]);
+/// ```
+///
+/// # Serde
+///
+/// With the `serde` feature, nodes marked with `#[serde]` (before any other attribute) implement
+/// `Serialize` and `Deserialize` as their inner value, so that lists of them can be serialized:
+///
+/// ```ignore
+/// def_node! {
+///     #[serde]
+///     #[derive(Debug)]
+///     pub struct TaskNode(u32);
+/// }
 /// ```
 ///
 #[macro_export(local_inner_macros)]
 macro_rules! def_node {
+    (#[serde] $(#[$meta:meta])* $vis:vis struct $name:ident($type:ty); $($t:tt)*) => {
+        __def_node_internal!($(#[$meta])* $vis struct $name($type););
+        __def_node_serde!($name($type));
+        def_node!($($t)*);
+    };
+    (#[serde] $(#[$meta:meta])* $vis:vis struct $name:ident<$gen:ident>($type:ty); $($t:tt)*) => {
+        __def_node_internal!($(#[$meta])* $vis struct $name<$gen>($type););
+        __def_node_serde!($name<$gen>($type));
+        def_node!($($t)*);
+    };
     ($(#[$meta:meta])* $vis:vis struct $name:ident($type:ty); $($t:tt)*) => {
         __def_node_internal!($(#[$meta])* $vis struct $name($type););
         def_node!($($t)*);
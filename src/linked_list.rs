@@ -6,16 +6,32 @@
 //!
 //! TODO: This module is a work in progress.
 
-extern crate alloc;
-
-use alloc::{boxed::Box, sync::Arc};
-use core::{iter, ptr::NonNull};
+#[cfg(feature = "alloc")]
+use alloc::{boxed::Box, rc::Rc, sync::Arc};
+#[cfg(feature = "allocator_api")]
+use core::alloc::Allocator;
+use core::{
+    cmp::Ordering,
+    fmt,
+    hash::{Hash, Hasher},
+    iter,
+    ops::Deref,
+    pin::Pin,
+    ptr::NonNull,
+};
 
 use crate::{raw_list, raw_list::RawList, GetLinks, Links};
 
 // TODO: Use the one from `kernel::file_operations::PointerWrapper` instead.
 /// Wraps an object to be inserted in a linked list.
-pub trait Wrapper<T: ?Sized> {
+///
+/// # Safety
+///
+/// Implementers must ensure that the pointer returned by [`Wrapper::into_pointer`] points to a
+/// valid object, which isn't moved, freed or mutated (except through interior mutability) until
+/// the pointer is passed back to [`Wrapper::from_pointer`]. [`Wrapper::as_ref`] must return a
+/// reference to that same object, i.e., the one the pointer would point to.
+pub unsafe trait Wrapper<T: ?Sized> {
     /// Converts the wrapped object into a pointer that represents it.
     fn into_pointer(self) -> NonNull<T>;
 
@@ -30,7 +46,10 @@ pub trait Wrapper<T: ?Sized> {
     fn as_ref(&self) -> &T;
 }
 
-impl<T: ?Sized> Wrapper<T> for Box<T> {
+#[cfg(all(feature = "alloc", not(feature = "allocator_api")))]
+// SAFETY: The box owns its object, which stays in place until the box is recreated from the
+// pointer and is only mutated through it.
+unsafe impl<T: ?Sized> Wrapper<T> for Box<T> {
     #[inline]
     fn into_pointer(self) -> NonNull<T> {
         NonNull::new(Box::into_raw(self)).unwrap()
@@ -47,7 +66,54 @@ impl<T: ?Sized> Wrapper<T> for Box<T> {
     }
 }
 
-impl<T: ?Sized> Wrapper<T> for Arc<T> {
+/// An allocator whose instances are interchangeable.
+///
+/// Boxes from such allocators can be put on lists: only the type of the allocator is kept while
+/// the box is on a list, and [`Wrapper::from_pointer`] frees it with `A::default()`.
+///
+/// # Safety
+///
+/// Implementers must ensure that `Self::default()` can free memory allocated by any other
+/// instance, and that dropping an instance doesn't free memory allocated by it, as is the case for
+/// zero-sized handles to global or per-CPU allocators.
+#[cfg(feature = "allocator_api")]
+pub unsafe trait InterchangeableAllocator: Allocator + Default {}
+
+// SAFETY: There is a single global allocator, which outlives all instances of `Global`.
+#[cfg(feature = "allocator_api")]
+unsafe impl InterchangeableAllocator for alloc::alloc::Global {}
+
+// SAFETY: There is a single system allocator, which outlives all instances of `System`.
+#[cfg(all(feature = "allocator_api", feature = "std"))]
+unsafe impl InterchangeableAllocator for std::alloc::System {}
+
+#[cfg(feature = "allocator_api")]
+// SAFETY: The box owns its object, which stays in place until the box is recreated from the
+// pointer and is only mutated through it.
+unsafe impl<T: ?Sized, A: InterchangeableAllocator> Wrapper<T> for Box<T, A> {
+    #[inline]
+    fn into_pointer(self) -> NonNull<T> {
+        let (ptr, _alloc) = Box::into_raw_with_allocator(self);
+        NonNull::new(ptr).unwrap()
+    }
+
+    #[inline]
+    unsafe fn from_pointer(ptr: NonNull<T>) -> Self {
+        // SAFETY: The pointer comes from a box with an allocator of type `A`, which any default
+        // instance can free by the safety requirements of `InterchangeableAllocator`.
+        unsafe { Box::from_raw_in(ptr.as_ptr(), A::default()) }
+    }
+
+    #[inline]
+    fn as_ref(&self) -> &T {
+        AsRef::as_ref(self)
+    }
+}
+
+#[cfg(feature = "alloc")]
+// SAFETY: The pointer owns a reference count, which keeps the shared object alive and in place
+// until the `Arc` is recreated from it.
+unsafe impl<T: ?Sized> Wrapper<T> for Arc<T> {
     #[inline]
     fn into_pointer(self) -> NonNull<T> {
         NonNull::new(Arc::into_raw(self) as _).unwrap()
@@ -65,7 +131,8 @@ impl<T: ?Sized> Wrapper<T> for Arc<T> {
     }
 }
 
-impl<T: ?Sized> Wrapper<T> for &T {
+// SAFETY: The object is borrowed immutably for at least as long as the pointer is used.
+unsafe impl<T: ?Sized> Wrapper<T> for &T {
     #[inline]
     fn into_pointer(self) -> NonNull<T> {
         NonNull::from(self)
@@ -82,12 +149,221 @@ impl<T: ?Sized> Wrapper<T> for &T {
     }
 }
 
+#[cfg(feature = "alloc")]
+// SAFETY: The pointer owns a reference count, which keeps the shared object alive and in place
+// until the `Rc` is recreated from it.
+unsafe impl<T: ?Sized> Wrapper<T> for Rc<T> {
+    #[inline]
+    fn into_pointer(self) -> NonNull<T> {
+        NonNull::new(Rc::into_raw(self) as _).unwrap()
+    }
+
+    #[inline]
+    unsafe fn from_pointer(ptr: NonNull<T>) -> Self {
+        // SAFETY: The safety requirements of `from_pointer` satisfy the ones from `Rc::from_raw`.
+        unsafe { Rc::from_raw(ptr.as_ptr() as _) }
+    }
+
+    #[inline]
+    fn as_ref(&self) -> &T {
+        AsRef::as_ref(self)
+    }
+}
+
+/// Exclusive references can be put on lists, which then hold the only way to access the object
+/// until it is removed and the reference is handed back.
+// SAFETY: The exclusive borrow of the object is given up to the pointer for at least as long as
+// it is used.
+unsafe impl<T: ?Sized> Wrapper<T> for &mut T {
+    #[inline]
+    fn into_pointer(self) -> NonNull<T> {
+        NonNull::from(self)
+    }
+
+    #[inline]
+    unsafe fn from_pointer(ptr: NonNull<T>) -> Self {
+        // SAFETY: The pointer comes from an exclusive reference with the same lifetime, which was
+        // given up in `into_pointer`, and the list only handed out shared references derived from
+        // it while the entry was on the list, which can't be used anymore.
+        unsafe { &mut *ptr.as_ptr() }
+    }
+
+    #[inline]
+    fn as_ref(&self) -> &T {
+        self
+    }
+}
+
+/// A pointer to an object that is owned elsewhere, e.g. by C code.
+///
+/// Lists never free the objects of handles: removing a handle from a list just hands it back, and
+/// dropping it does nothing.
+pub struct RawHandle<T: ?Sized> {
+    ptr: NonNull<T>,
+}
+
+impl<T: ?Sized> RawHandle<T> {
+    /// Creates a handle to the object pointed to by `ptr`.
+    ///
+    /// # Safety
+    ///
+    /// Callers must ensure that `ptr` points to a valid object that outlives the handle, its
+    /// copies and any list it is on, and that the object isn't mutated except through interior
+    /// mutability meanwhile.
+    pub const unsafe fn new(ptr: NonNull<T>) -> Self {
+        Self { ptr }
+    }
+
+    /// Returns the pointer to the object.
+    pub const fn as_ptr(&self) -> NonNull<T> {
+        self.ptr
+    }
+}
+
+impl<T: ?Sized> Clone for RawHandle<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T: ?Sized> Copy for RawHandle<T> {}
+
+impl<T: ?Sized> Deref for RawHandle<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // SAFETY: The object is valid for as long as the handle by the safety requirements of
+        // `RawHandle::new`.
+        unsafe { self.ptr.as_ref() }
+    }
+}
+
+impl<T: ?Sized> fmt::Debug for RawHandle<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("RawHandle").field(&self.ptr).finish()
+    }
+}
+
+// SAFETY: A handle only gives shared access to its object, like `&T`.
+unsafe impl<T: ?Sized + Sync> Send for RawHandle<T> {}
+
+// SAFETY: A handle only gives shared access to its object, like `&T`.
+unsafe impl<T: ?Sized + Sync> Sync for RawHandle<T> {}
+
+// SAFETY: The object outlives the handle and any list it is on, and isn't mutated meanwhile, by
+// the safety requirements of `RawHandle::new`.
+unsafe impl<T: ?Sized> Wrapper<T> for RawHandle<T> {
+    #[inline]
+    fn into_pointer(self) -> NonNull<T> {
+        self.ptr
+    }
+
+    #[inline]
+    unsafe fn from_pointer(ptr: NonNull<T>) -> Self {
+        // SAFETY: The pointer comes from a handle, whose safety requirements still hold.
+        unsafe { Self::new(ptr) }
+    }
+
+    #[inline]
+    fn as_ref(&self) -> &T {
+        self
+    }
+}
+
+#[cfg(all(feature = "alloc", not(feature = "allocator_api")))]
+// SAFETY: Pinned boxes uphold the requirements like unpinned ones, and never move the object.
+unsafe impl<T: ?Sized> Wrapper<T> for Pin<Box<T>> {
+    #[inline]
+    fn into_pointer(self) -> NonNull<T> {
+        // SAFETY: The pointer is only used to access the object in place, it is never moved.
+        let boxed = unsafe { Pin::into_inner_unchecked(self) };
+        NonNull::new(Box::into_raw(boxed)).unwrap()
+    }
+
+    #[inline]
+    unsafe fn from_pointer(ptr: NonNull<T>) -> Self {
+        // SAFETY: The pointer comes from a pinned box, so the object was pinned already.
+        unsafe { Pin::new_unchecked(Box::from_raw(ptr.as_ptr())) }
+    }
+
+    #[inline]
+    fn as_ref(&self) -> &T {
+        Pin::get_ref(Pin::as_ref(self))
+    }
+}
+
+#[cfg(feature = "allocator_api")]
+// SAFETY: Pinned boxes uphold the requirements like unpinned ones, and never move the object.
+unsafe impl<T: ?Sized, A: InterchangeableAllocator> Wrapper<T> for Pin<Box<T, A>> {
+    #[inline]
+    fn into_pointer(self) -> NonNull<T> {
+        // SAFETY: The pointer is only used to access the object in place, it is never moved.
+        let boxed = unsafe { Pin::into_inner_unchecked(self) };
+        Wrapper::into_pointer(boxed)
+    }
+
+    #[inline]
+    unsafe fn from_pointer(ptr: NonNull<T>) -> Self {
+        // SAFETY: The pointer comes from a pinned box, so the object was pinned already, and the
+        // safety requirements are the same as the ones of the unpinned box.
+        unsafe { Pin::new_unchecked(<Box<T, A> as Wrapper<T>>::from_pointer(ptr)) }
+    }
+
+    #[inline]
+    fn as_ref(&self) -> &T {
+        Pin::get_ref(Pin::as_ref(self))
+    }
+}
+
+#[cfg(feature = "alloc")]
+// SAFETY: Pinned `Arc`s uphold the requirements like unpinned ones.
+unsafe impl<T: ?Sized> Wrapper<T> for Pin<Arc<T>> {
+    #[inline]
+    fn into_pointer(self) -> NonNull<T> {
+        // SAFETY: The pointer is only used to access the object in place, it is never moved.
+        let arc = unsafe { Pin::into_inner_unchecked(self) };
+        NonNull::new(Arc::into_raw(arc) as _).unwrap()
+    }
+
+    #[inline]
+    unsafe fn from_pointer(ptr: NonNull<T>) -> Self {
+        // SAFETY: The pointer comes from a pinned `Arc`, so the object was pinned already, and
+        // the safety requirements of `from_pointer` satisfy the ones from `Arc::from_raw`.
+        unsafe { Pin::new_unchecked(Arc::from_raw(ptr.as_ptr() as _)) }
+    }
+
+    #[inline]
+    fn as_ref(&self) -> &T {
+        Pin::get_ref(Pin::as_ref(self))
+    }
+}
+
+// SAFETY: Pinned references uphold the requirements like unpinned ones.
+unsafe impl<T: ?Sized> Wrapper<T> for Pin<&T> {
+    #[inline]
+    fn into_pointer(self) -> NonNull<T> {
+        NonNull::from(Pin::get_ref(self))
+    }
+
+    #[inline]
+    unsafe fn from_pointer(ptr: NonNull<T>) -> Self {
+        // SAFETY: The pointer comes from a pinned reference, so the object was pinned already.
+        unsafe { Pin::new_unchecked(&*ptr.as_ptr()) }
+    }
+
+    #[inline]
+    fn as_ref(&self) -> &T {
+        Pin::get_ref(*self)
+    }
+}
+
 /// A descriptor of wrapped list elements.
 pub trait GetLinksWrapped: GetLinks {
     /// Specifies which wrapper (e.g., `Box` and `Arc`) wraps the list entries.
     type Wrapped: Wrapper<Self::EntryType>;
 }
 
+#[cfg(all(feature = "alloc", not(feature = "allocator_api")))]
 impl<T: ?Sized> GetLinksWrapped for Box<T>
 where
     Box<T>: GetLinks,
@@ -95,6 +371,15 @@ where
     type Wrapped = Box<<Box<T> as GetLinks>::EntryType>;
 }
 
+#[cfg(feature = "allocator_api")]
+impl<T: ?Sized, A: InterchangeableAllocator> GetLinksWrapped for Box<T, A>
+where
+    Box<T, A>: GetLinks,
+{
+    type Wrapped = Box<<Box<T, A> as GetLinks>::EntryType, A>;
+}
+
+#[cfg(all(feature = "alloc", not(feature = "allocator_api")))]
 impl<T: GetLinks + ?Sized> GetLinks for Box<T> {
     type EntryType = T::EntryType;
 
@@ -104,6 +389,17 @@ impl<T: GetLinks + ?Sized> GetLinks for Box<T> {
     }
 }
 
+#[cfg(feature = "allocator_api")]
+impl<T: GetLinks + ?Sized, A: Allocator> GetLinks for Box<T, A> {
+    type EntryType = T::EntryType;
+
+    #[inline]
+    fn get_links(data: &Self::EntryType) -> &Links<Self::EntryType> {
+        <T as GetLinks>::get_links(data)
+    }
+}
+
+#[cfg(feature = "alloc")]
 impl<T: ?Sized> GetLinksWrapped for Arc<T>
 where
     Arc<T>: GetLinks,
@@ -111,6 +407,7 @@ where
     type Wrapped = Arc<<Arc<T> as GetLinks>::EntryType>;
 }
 
+#[cfg(feature = "alloc")]
 impl<T: GetLinks + ?Sized> GetLinks for Arc<T> {
     type EntryType = T::EntryType;
 
@@ -120,12 +417,122 @@ impl<T: GetLinks + ?Sized> GetLinks for Arc<T> {
     }
 }
 
+impl<T: GetLinks + ?Sized> GetLinks for &T {
+    type EntryType = T::EntryType;
+
+    #[inline]
+    fn get_links(data: &Self::EntryType) -> &Links<Self::EntryType> {
+        <T as GetLinks>::get_links(data)
+    }
+}
+
+impl<'a, T: ?Sized> GetLinksWrapped for &'a T
+where
+    &'a T: GetLinks,
+{
+    type Wrapped = &'a <&'a T as GetLinks>::EntryType;
+}
+
+#[cfg(feature = "alloc")]
+impl<T: ?Sized> GetLinksWrapped for Rc<T>
+where
+    Rc<T>: GetLinks,
+{
+    type Wrapped = Rc<<Rc<T> as GetLinks>::EntryType>;
+}
+
+#[cfg(feature = "alloc")]
+impl<T: GetLinks + ?Sized> GetLinks for Rc<T> {
+    type EntryType = T::EntryType;
+
+    #[inline]
+    fn get_links(data: &Self::EntryType) -> &Links<Self::EntryType> {
+        <T as GetLinks>::get_links(data)
+    }
+}
+
+impl<T: GetLinks + ?Sized> GetLinks for &mut T {
+    type EntryType = T::EntryType;
+
+    #[inline]
+    fn get_links(data: &Self::EntryType) -> &Links<Self::EntryType> {
+        <T as GetLinks>::get_links(data)
+    }
+}
+
+impl<'a, T: ?Sized> GetLinksWrapped for &'a mut T
+where
+    &'a mut T: GetLinks,
+{
+    type Wrapped = &'a mut <&'a mut T as GetLinks>::EntryType;
+}
+
+impl<T: GetLinks + ?Sized> GetLinks for RawHandle<T> {
+    type EntryType = T::EntryType;
+
+    #[inline]
+    fn get_links(data: &Self::EntryType) -> &Links<Self::EntryType> {
+        <T as GetLinks>::get_links(data)
+    }
+}
+
+impl<T: ?Sized> GetLinksWrapped for RawHandle<T>
+where
+    RawHandle<T>: GetLinks,
+{
+    type Wrapped = RawHandle<<RawHandle<T> as GetLinks>::EntryType>;
+}
+
+impl<P: GetLinks> GetLinks for Pin<P> {
+    type EntryType = P::EntryType;
+
+    #[inline]
+    fn get_links(data: &Self::EntryType) -> &Links<Self::EntryType> {
+        <P as GetLinks>::get_links(data)
+    }
+}
+
+#[cfg(all(feature = "alloc", not(feature = "allocator_api")))]
+impl<T: ?Sized> GetLinksWrapped for Pin<Box<T>>
+where
+    Box<T>: GetLinks,
+{
+    type Wrapped = Pin<Box<<Box<T> as GetLinks>::EntryType>>;
+}
+
+#[cfg(feature = "allocator_api")]
+impl<T: ?Sized, A: InterchangeableAllocator> GetLinksWrapped for Pin<Box<T, A>>
+where
+    Box<T, A>: GetLinks,
+{
+    type Wrapped = Pin<Box<<Box<T, A> as GetLinks>::EntryType, A>>;
+}
+
+#[cfg(feature = "alloc")]
+impl<T: ?Sized> GetLinksWrapped for Pin<Arc<T>>
+where
+    Arc<T>: GetLinks,
+{
+    type Wrapped = Pin<Arc<<Arc<T> as GetLinks>::EntryType>>;
+}
+
+impl<'a, T: ?Sized> GetLinksWrapped for Pin<&'a T>
+where
+    &'a T: GetLinks,
+{
+    type Wrapped = Pin<&'a <&'a T as GetLinks>::EntryType>;
+}
+
 /// A linked list.
 ///
 /// Elements in the list are wrapped and ownership is transferred to the list while the element is
 /// in the list.
+///
+/// Objects that must not move while they are on a list, such as stack-allocated or embedded
+/// nodes, can be linked through pinned wrappers, e.g. `List<Pin<&T>>`. The borrow held by the
+/// list then guarantees that the nodes outlive it, so no `unsafe` is needed.
 pub struct List<G: GetLinksWrapped> {
-    list: RawList<G>,
+    pub(crate) list: RawList<G>,
 }
 
 impl<G: GetLinksWrapped> List<G> {
@@ -154,7 +561,7 @@ impl<G: GetLinksWrapped> List<G> {
         let ptr = data.into_pointer();
 
         // SAFETY: We took ownership of the entry, so it is safe to insert it.
-        if !unsafe { self.list.push_back(ptr.as_ref()) } {
+        if !unsafe { self.list.push_back_internal(ptr, false) } {
             // If insertion failed, rebuild object so that it can be freed.
             // SAFETY: We just called `into_pointer` above.
             unsafe { G::Wrapped::from_pointer(ptr) };
@@ -169,7 +576,7 @@ impl<G: GetLinksWrapped> List<G> {
         let ptr = data.into_pointer();
 
         // SAFETY: We took ownership of the entry, so it is safe to insert it.
-        if !unsafe { self.list.push_front(ptr.as_ref()) } {
+        if !unsafe { self.list.push_back_internal(ptr, true) } {
             // If insertion failed, rebuild object so that it can be freed.
             unsafe { G::Wrapped::from_pointer(ptr) };
         }
@@ -186,7 +593,7 @@ impl<G: GetLinksWrapped> List<G> {
     pub unsafe fn insert_after(&mut self, existing: &G::Wrapped, data: G::Wrapped) {
         let ptr = data.into_pointer();
         let entry = Wrapper::as_ref(existing);
-        if unsafe { !self.list.insert_after(entry, ptr.as_ref()) } {
+        if unsafe { !self.list.insert_after_internal(entry, ptr) } {
             // If insertion failed, rebuild object so that it can be freed.
             unsafe { G::Wrapped::from_pointer(ptr) };
         }
@@ -199,12 +606,10 @@ impl<G: GetLinksWrapped> List<G> {
     /// Callers must ensure that `data` is either on this list or in no list. It being on another
     /// list leads to memory unsafety.
     pub unsafe fn remove(&mut self, data: &G::Wrapped) -> Option<G::Wrapped> {
-        let entry_ref = Wrapper::as_ref(data);
-        if unsafe { self.list.remove(entry_ref) } {
-            Some(unsafe { G::Wrapped::from_pointer(NonNull::from(entry_ref)) })
-        } else {
-            None
-        }
+        // SAFETY: The safety requirements are the same as `RawList::remove_internal`.
+        let ptr = unsafe { self.list.remove_internal(Wrapper::as_ref(data)) }?;
+        // SAFETY: The pointer is the one stored on the list, which came from `into_pointer`.
+        Some(unsafe { G::Wrapped::from_pointer(ptr) })
     }
 
     /// Removes the element currently at the front of the list and returns it.
@@ -216,10 +621,53 @@ impl<G: GetLinksWrapped> List<G> {
         Some(unsafe { G::Wrapped::from_pointer(front) })
     }
 
+    /// Removes the element currently at the back of the list and returns it.
+    ///
+    /// Returns `None` if the list is empty.
+    pub fn pop_back(&mut self) -> Option<G::Wrapped> {
+        let back = self.list.pop_back()?;
+        // SAFETY: Elements on the list were inserted after a call to `into_pointer `.
+        Some(unsafe { G::Wrapped::from_pointer(back) })
+    }
+
     /// Returns a mutable cursor starting on the first (front) element of the list.
     pub fn cursor_front_mut(&mut self) -> CursorMut<'_, G> {
         CursorMut::new(self.list.cursor_front_mut())
     }
+
+    /// Checks that the links of the entries on the list are consistent.
+    ///
+    /// See [`RawList::check_invariants`].
+    pub fn check_invariants(&self) -> Result<(), raw_list::ListCorruption> {
+        self.list.check_invariants()
+    }
+
+    /// Returns a new list with the objects returned by `f` for each entry, in the same order.
+    ///
+    /// Entries can't be on two lists through the same links, so `f` must return newly allocated
+    /// objects; an object that is already on a list is dropped, as in [`List::push_back`].
+    pub fn clone_into_with(&self, f: impl FnMut(&G::EntryType) -> G::Wrapped) -> Self {
+        let mut list = Self::new();
+        for data in self.iter().map(f) {
+            list.push_back(data);
+        }
+        list
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T> Clone for List<Box<T>>
+where
+    T: GetLinks + ?Sized,
+    T::EntryType: Clone + Sized,
+{
+    /// Returns a list of boxed clones of the entries.
+    ///
+    /// The links of the clones are reset by [`Links::clone`], so they are not on any list until
+    /// they are pushed to the new one.
+    fn clone(&self) -> Self {
+        self.clone_into_with(|entry| Box::new(entry.clone()))
+    }
 }
 
 impl<G: GetLinksWrapped> Default for List<G> {
@@ -228,12 +676,154 @@ impl<G: GetLinksWrapped> Default for List<G> {
     }
 }
 
+impl<G: GetLinksWrapped> fmt::Debug for List<G>
+where
+    G::EntryType: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.list, f)
+    }
+}
+
+/// Lists are compared entry by entry, as sequences.
+impl<G: GetLinksWrapped> PartialEq for List<G>
+where
+    G::EntryType: PartialEq,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.list == other.list
+    }
+}
+
+impl<G: GetLinksWrapped> Eq for List<G> where G::EntryType: Eq {}
+
+/// Lists are ordered lexicographically by their entries.
+impl<G: GetLinksWrapped> PartialOrd for List<G>
+where
+    G::EntryType: PartialOrd,
+{
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        self.list.partial_cmp(&other.list)
+    }
+}
+
+impl<G: GetLinksWrapped> Ord for List<G>
+where
+    G::EntryType: Ord,
+{
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.list.cmp(&other.list)
+    }
+}
+
+impl<G: GetLinksWrapped> Hash for List<G>
+where
+    G::EntryType: Hash,
+{
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.list.hash(state);
+    }
+}
+
+/// Compares the payloads of the entries, that is, what they dereference to, with a slice.
+///
+/// This allows comparing lists of [`def_node!`](crate::def_node) types with slices of their inner
+/// values.
+impl<G, T> PartialEq<[T]> for List<G>
+where
+    G: GetLinksWrapped,
+    G::EntryType: Deref,
+    <G::EntryType as Deref>::Target: PartialEq<T>,
+{
+    fn eq(&self, other: &[T]) -> bool {
+        self.iter().map(Deref::deref).eq(other)
+    }
+}
+
+/// Compares the payloads of the entries with an array, as with slices.
+impl<G, T, const N: usize> PartialEq<[T; N]> for List<G>
+where
+    G: GetLinksWrapped,
+    G::EntryType: Deref,
+    <G::EntryType as Deref>::Target: PartialEq<T>,
+{
+    fn eq(&self, other: &[T; N]) -> bool {
+        *self == other[..]
+    }
+}
+
+/// Serializes the list as a sequence of its entries.
+#[cfg(feature = "serde")]
+impl<G: GetLinksWrapped> serde::Serialize for List<G>
+where
+    G::EntryType: serde::Serialize,
+{
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeSeq;
+
+        let mut seq = serializer.serialize_seq(Some(self.iter().count()))?;
+        for entry in self.iter() {
+            seq.serialize_element(entry)?;
+        }
+        seq.end()
+    }
+}
+
+/// Deserializes a sequence of entries into a list of newly allocated boxes.
+///
+/// Entries are expected to deserialize with links that aren't on any list, as the constructors of
+/// [`def_node!`](crate::def_node) types or `#[serde(skip)]` on a [`Links`] field do.
+#[cfg(all(feature = "alloc", feature = "serde"))]
+impl<'de, T> serde::Deserialize<'de> for List<Box<T>>
+where
+    T: GetLinks + ?Sized,
+    T::EntryType: serde::Deserialize<'de> + Sized,
+{
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct ListVisitor<T: ?Sized>(core::marker::PhantomData<fn() -> Box<T>>);
+
+        impl<'de, T> serde::de::Visitor<'de> for ListVisitor<T>
+        where
+            T: GetLinks + ?Sized,
+            T::EntryType: serde::Deserialize<'de> + Sized,
+        {
+            type Value = List<Box<T>>;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("a sequence of list entries")
+            }
+
+            fn visit_seq<A: serde::de::SeqAccess<'de>>(
+                self,
+                mut seq: A,
+            ) -> Result<Self::Value, A::Error> {
+                let mut list = List::new();
+                while let Some(entry) = seq.next_element()? {
+                    list.push_back(Box::new(entry));
+                }
+                Ok(list)
+            }
+        }
+
+        deserializer.deserialize_seq(ListVisitor(core::marker::PhantomData))
+    }
+}
+
 impl<G: GetLinksWrapped> Drop for List<G> {
     fn drop(&mut self) {
         while self.pop_front().is_some() {}
     }
 }
 
+// SAFETY: The list owns its wrapped entries and drops them on whichever thread it is dropped, so
+// it can be sent to other threads only when they can. `RawList` alone would allow it for entries
+// that are `Send`, even when the wrappers, e.g. `Rc`, are not.
+unsafe impl<G: GetLinksWrapped> Send for List<G> where G::Wrapped: Send {}
+
+// SAFETY: Shared references to the list only hand out shared references to its entries, which is
+// as much as shared references to the wrappers allow.
+unsafe impl<G: GetLinksWrapped> Sync for List<G> where G::Wrapped: Sync {}
+
 /// A list cursor that allows traversing a linked list and inspecting & mutating elements.
 pub struct CursorMut<'a, G: GetLinksWrapped> {
     cursor: raw_list::CursorMut<'a, G>,
@@ -245,7 +835,10 @@ impl<'a, G: GetLinksWrapped> CursorMut<'a, G> {
     }
 
     /// Returns the element the cursor is currently positioned on.
-    pub fn current(&mut self) -> Option<&mut G::EntryType> {
+    ///
+    /// Entries are only accessible through shared references while they are on the list, as
+    /// other entries hold pointers to them.
+    pub fn current(&self) -> Option<&G::EntryType> {
         self.cursor.current()
     }
 
@@ -260,12 +853,12 @@ impl<'a, G: GetLinksWrapped> CursorMut<'a, G> {
     }
 
     /// Returns the element immediately after the one the cursor is positioned on.
-    pub fn peek_next(&mut self) -> Option<&mut G::EntryType> {
+    pub fn peek_next(&self) -> Option<&G::EntryType> {
         self.cursor.peek_next()
     }
 
     /// Returns the element immediately before the one the cursor is positioned on.
-    pub fn peek_prev(&mut self) -> Option<&mut G::EntryType> {
+    pub fn peek_prev(&self) -> Option<&G::EntryType> {
         self.cursor.peek_prev()
     }
 
@@ -275,6 +868,15 @@ impl<'a, G: GetLinksWrapped> CursorMut<'a, G> {
     }
 }
 
+impl<G: GetLinksWrapped> fmt::Debug for CursorMut<'_, G>
+where
+    G::EntryType: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.cursor, f)
+    }
+}
+
 /// An iterator for the linked list.
 pub struct Iterator<'a, G: GetLinksWrapped> {
     iter: raw_list::Iterator<'a, G>,
@@ -302,10 +904,20 @@ impl<G: GetLinksWrapped> iter::DoubleEndedIterator for Iterator<'_, G> {
     }
 }
 
-#[cfg(test)]
+impl<G: GetLinksWrapped> fmt::Debug for Iterator<'_, G>
+where
+    G::EntryType: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.iter, f)
+    }
+}
+
+#[cfg(all(test, feature = "alloc", not(loom)))]
 mod tests {
     use super::{GetLinks, Links, List};
 
+    #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
     struct Example {
         inner: usize,
         links: Links<Self>,
@@ -351,4 +963,344 @@ mod tests {
         }
         assert_list_contents(&list, MAX);
     }
+
+    #[test]
+    fn test_pinned_wrappers() {
+        use core::pin::Pin;
+        use std::sync::Arc;
+
+        let mut list = List::<Pin<Box<Example>>>::new();
+        for n in 1..=3 {
+            list.push_back(Box::pin(Example {
+                inner: n,
+                links: Links::new(),
+            }));
+        }
+        let front = list.pop_front().unwrap();
+        assert_eq!(front.inner, 1);
+        list.push_back(front);
+        assert_eq!(list.iter().map(|e| e.inner).collect::<Vec<_>>(), [2, 3, 1]);
+
+        let shared = Arc::pin(Example {
+            inner: 1,
+            links: Links::new(),
+        });
+        let mut list = List::<Pin<Arc<Example>>>::new();
+        list.push_back(shared.clone());
+        // Already on a list, so this is dropped.
+        list.push_back(shared.clone());
+        assert_eq!(Arc::strong_count(&Pin::into_inner(shared.clone())), 3);
+        // SAFETY: `shared` is on this list.
+        let removed = unsafe { list.remove(&shared) }.unwrap();
+        assert!(list.is_empty());
+        drop(removed);
+        assert_eq!(Arc::strong_count(&Pin::into_inner(shared)), 1);
+    }
+
+    #[test]
+    fn test_stack_nodes() {
+        use core::pin::{pin, Pin};
+
+        let a = pin!(Example {
+            inner: 1,
+            links: Links::new(),
+        });
+        let b = pin!(Example {
+            inner: 2,
+            links: Links::new(),
+        });
+        let (a, b) = (a.into_ref(), b.into_ref());
+
+        let mut list = List::<Pin<&Example>>::new();
+        list.push_back(b);
+        list.push_front(a);
+        // Already on the list, so this is ignored.
+        list.push_back(a);
+        assert_eq!(list.iter().map(|e| e.inner).collect::<Vec<_>>(), [1, 2]);
+
+        let mut list2 = List::<&Example>::new();
+        // `a` is still on the first list.
+        list2.push_back(Pin::get_ref(a));
+        assert!(list2.is_empty());
+        assert_eq!(list.pop_front().unwrap().inner, 1);
+        list2.push_back(Pin::get_ref(a));
+        assert_eq!(list2.iter().map(|e| e.inner).collect::<Vec<_>>(), [1]);
+    }
+
+    #[test]
+    fn test_cursor_overlapping_peeks() {
+        let mut list = List::<Box<Example>>::new();
+        for n in 1..=3 {
+            list.push_back(Box::new(Example {
+                inner: n,
+                links: Links::new(),
+            }));
+        }
+
+        let mut cursor = list.cursor_front_mut();
+        cursor.move_next();
+        // References to neighbouring and current entries can be held at the same time.
+        let (prev, cur, next) = (cursor.peek_prev(), cursor.current(), cursor.peek_next());
+        assert_eq!(prev.unwrap().inner, 1);
+        assert_eq!(cur.unwrap().inner, 2);
+        assert_eq!(next.unwrap().inner, 3);
+
+        assert_eq!(cursor.remove_current().unwrap().inner, 2);
+        assert_eq!(cursor.current().unwrap().inner, 3);
+        assert_eq!(list.iter().map(|e| e.inner).collect::<Vec<_>>(), [1, 3]);
+    }
+
+    #[test]
+    fn test_debug() {
+        #[derive(Debug)]
+        struct Queue {
+            list: List<Box<Example>>,
+        }
+
+        let mut queue = Queue { list: List::new() };
+        assert_eq!(format!("{queue:?}"), "Queue { list: [] }");
+        for n in 1..=3 {
+            queue.list.push_back(Box::new(Example {
+                inner: n,
+                links: Links::new(),
+            }));
+        }
+        let unlinked = Example {
+            inner: 4,
+            links: Links::new(),
+        };
+        assert_eq!(
+            format!("{unlinked:?}"),
+            "Example { inner: 4, links: Links(unlinked) }"
+        );
+
+        let mut iter = queue.list.iter();
+        iter.next();
+        assert_eq!(
+            format!("{iter:?}"),
+            "Iterator([Example { inner: 2, links: Links(linked) }, \
+             Example { inner: 3, links: Links(linked) }])"
+        );
+
+        let mut cursor = queue.list.cursor_front_mut();
+        cursor.move_next();
+        assert_eq!(
+            format!("{cursor:?}"),
+            "CursorMut { index: Some(1), current: Some(Example { inner: 2, links: Links(linked) }) }"
+        );
+        cursor.move_next();
+        cursor.move_next();
+        assert_eq!(
+            format!("{cursor:?}"),
+            "CursorMut { index: None, current: None }"
+        );
+    }
+
+    #[test]
+    fn test_clone() {
+        use std::sync::Arc;
+
+        let mut list = List::<Box<Example>>::new();
+        for n in 1..=3 {
+            list.push_back(Box::new(Example {
+                inner: n,
+                links: Links::new(),
+            }));
+        }
+        let mut copy = list.clone();
+        assert_list_contents(&copy, 3);
+        assert_eq!(copy.check_invariants(), Ok(()));
+        copy.pop_front();
+        assert_list_contents(&list, 3);
+
+        let mut shared = List::<Arc<Example>>::new();
+        for e in list.iter() {
+            shared.push_back(Arc::new(e.clone()));
+        }
+        let snapshot = shared.clone_into_with(|e| Arc::new(e.clone()));
+        shared.pop_front();
+        let inner: Vec<_> = snapshot.iter().map(|e| e.inner).collect();
+        assert_eq!(inner, [1, 2, 3]);
+
+        // Objects that are already on a list are dropped rather than inserted again.
+        let front = Arc::new(Example {
+            inner: 0,
+            links: Links::new(),
+        });
+        shared.push_front(front.clone());
+        let same = shared.clone_into_with(|_| front.clone());
+        assert!(same.is_empty());
+        assert_eq!(Arc::strong_count(&front), 2);
+    }
+
+    #[test]
+    fn test_compare() {
+        use std::hash::{BuildHasher, RandomState};
+
+        let new_list = |values: &[usize]| {
+            let mut list = List::<Box<Example>>::new();
+            for &inner in values {
+                list.push_back(Box::new(Example {
+                    inner,
+                    links: Links::new(),
+                }));
+            }
+            list
+        };
+
+        let list = new_list(&[1, 2, 3]);
+        assert_eq!(list, new_list(&[1, 2, 3]));
+        assert_ne!(list, new_list(&[1, 2]));
+        assert!(list < new_list(&[1, 3]));
+        assert!(list > new_list(&[1, 2]));
+        assert!(new_list(&[]) < list);
+        assert_eq!(list.cmp(&list.clone()), core::cmp::Ordering::Equal);
+
+        let state = RandomState::new();
+        assert_eq!(state.hash_one(&list), state.hash_one(new_list(&[1, 2, 3])));
+        assert_ne!(state.hash_one(&list), state.hash_one(new_list(&[1, 2])));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde() {
+        use serde_test::{assert_tokens, Token};
+
+        crate::def_node! {
+            #[serde]
+            #[derive(Debug, PartialEq)]
+            struct Task(u32);
+            #[serde]
+            #[derive(Debug, PartialEq)]
+            struct Named<T>(T);
+        }
+
+        let mut list = List::<Box<Task>>::new();
+        list.push_back(Box::new(Task::new(1)));
+        list.push_back(Box::new(Task::new(2)));
+        assert_tokens(
+            &list,
+            &[
+                Token::Seq { len: Some(2) },
+                Token::U32(1),
+                Token::U32(2),
+                Token::SeqEnd,
+            ],
+        );
+        let front = list.pop_front().unwrap();
+        assert_eq!(*front.inner(), 1);
+        assert_eq!(front.into_inner(), 1);
+
+        let mut list = List::<Box<Named<&str>>>::new();
+        list.push_back(Box::new(Named::new("idle")));
+        assert_tokens(
+            &list,
+            &[
+                Token::Seq { len: Some(1) },
+                Token::BorrowedStr("idle"),
+                Token::SeqEnd,
+            ],
+        );
+        let front = list.pop_front().unwrap();
+        assert_eq!(*front.inner(), "idle");
+        assert_eq!(front.into_inner(), "idle");
+    }
+
+    #[cfg(feature = "allocator_api")]
+    #[test]
+    fn test_allocator_box() {
+        use core::{
+            alloc::{AllocError, Allocator, Layout},
+            ptr::NonNull,
+            sync::atomic::{AtomicUsize, Ordering},
+        };
+        use std::alloc::Global;
+
+        use super::InterchangeableAllocator;
+
+        static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+
+        #[derive(Default)]
+        struct Slab;
+
+        // SAFETY: Memory comes from and goes back to the global allocator.
+        unsafe impl Allocator for Slab {
+            fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+                ALLOCATED.fetch_add(1, Ordering::Relaxed);
+                Global.allocate(layout)
+            }
+
+            unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+                ALLOCATED.fetch_sub(1, Ordering::Relaxed);
+                // SAFETY: The memory was allocated by `Global` in `allocate`.
+                unsafe { Global.deallocate(ptr, layout) }
+            }
+        }
+
+        // SAFETY: `Slab` is a zero-sized handle to the global allocator.
+        unsafe impl InterchangeableAllocator for Slab {}
+
+        let mut list = List::<Box<Example, Slab>>::new();
+        for n in 1..=3 {
+            let links = Links::new();
+            list.push_back(Box::new_in(Example { inner: n, links }, Slab));
+        }
+        assert_eq!(ALLOCATED.load(Ordering::Relaxed), 3);
+        assert_eq!(list.pop_front().unwrap().inner, 1);
+        assert_eq!(list.iter().map(|e| e.inner).collect::<Vec<_>>(), [2, 3]);
+        drop(list);
+        assert_eq!(ALLOCATED.load(Ordering::Relaxed), 0);
+    }
+
+    #[test]
+    fn test_rc_mut_and_raw_handles() {
+        use core::ptr::NonNull;
+        use std::rc::Rc;
+
+        use super::RawHandle;
+
+        let new_example = |inner| Example {
+            inner,
+            links: Links::new(),
+        };
+
+        let shared = Rc::new(new_example(1));
+        let mut list = List::<Rc<Example>>::new();
+        list.push_back(shared.clone());
+        // Already on the list, so this is dropped.
+        list.push_back(shared.clone());
+        assert_eq!(Rc::strong_count(&shared), 2);
+        drop(list);
+        assert_eq!(Rc::strong_count(&shared), 1);
+
+        let (mut a, mut b) = (new_example(1), new_example(2));
+        let mut list = List::<&mut Example>::new();
+        list.push_back(&mut a);
+        list.push_back(&mut b);
+        let front = list.pop_front().unwrap();
+        front.inner = 10;
+        list.push_back(front);
+        assert_eq!(list.iter().map(|e| e.inner).collect::<Vec<_>>(), [2, 10]);
+        drop(list);
+        assert_eq!((a.inner, b.inner), (10, 2));
+
+        // Objects owned elsewhere, as if allocated by C code.
+        let objects = Box::into_raw(Box::new([new_example(1), new_example(2)]));
+        {
+            let mut list = List::<RawHandle<Example>>::new();
+            for i in 0..2 {
+                // SAFETY: The objects are valid until they are freed below, after the list is gone.
+                let ptr = unsafe { NonNull::new_unchecked(&raw mut (*objects)[i]) };
+                // SAFETY: Same as above.
+                list.push_front(unsafe { RawHandle::new(ptr) });
+            }
+            let handle = list.pop_front().unwrap();
+            assert_eq!(handle.inner, 2);
+            // SAFETY: The handle is in no list.
+            assert!(unsafe { list.remove(&handle) }.is_none());
+        }
+        // SAFETY: The list and the handles are gone, and the list didn't free the objects.
+        let objects = unsafe { Box::from_raw(objects) };
+        assert_eq!(objects[0].inner, 1);
+    }
 }
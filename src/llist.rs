@@ -0,0 +1,237 @@
+// SPDX-License-Identifier: GPL-2.0
+
+//! Lock-free lists.
+//!
+//! Based on linux/include/linux/llist.h: any number of producers push entries concurrently, and
+//! a consumer takes all of them at once.
+
+use core::{
+    ptr::{self, NonNull},
+    sync::atomic::{AtomicPtr, Ordering},
+};
+
+use crate::linked_list::{GetLinksWrapped, List, Wrapper};
+
+/// A lock-free multi-producer stack of wrapped entries.
+///
+/// Entries are pushed with [`LockFreeStack::push`] from any context (e.g., an interrupt handler)
+/// without locking, and are handed over to the consumer as a regular [`List`] by
+/// [`LockFreeStack::take_all`]. Entries use the same [`Links`](crate::Links) as [`List`], and
+/// the same node types can be used with both.
+///
+/// # Invariants
+///
+/// `head` is either null or points to the most recently pushed entry. The `next` links of the
+/// entries on the stack form a null-terminated chain, and the links of these entries are owned by
+/// the stack.
+pub struct LockFreeStack<G: GetLinksWrapped>
+where
+    G::EntryType: Sized,
+{
+    head: AtomicPtr<G::EntryType>,
+}
+
+impl<G: GetLinksWrapped> LockFreeStack<G>
+where
+    G::EntryType: Sized,
+{
+    /// Constructs a new empty stack.
+    pub const fn new() -> Self {
+        Self {
+            head: AtomicPtr::new(ptr::null_mut()),
+        }
+    }
+
+    /// Returns whether the stack is empty.
+    pub fn is_empty(&self) -> bool {
+        self.head.load(Ordering::Relaxed).is_null()
+    }
+
+    /// Pushes the given object onto the stack.
+    ///
+    /// It is dropped if it's already on this (or another) list; this can happen for
+    /// reference-counted objects, so dropping means decrementing the reference count.
+    ///
+    /// Returns whether the stack was empty before the object was pushed, so that the caller knows
+    /// when the consumer needs to be notified. Returns `false` if the object was dropped.
+    pub fn push(&self, data: G::Wrapped) -> bool {
+        let ptr = data.into_pointer();
+        // SAFETY: We took ownership of the entry, so it is valid.
+        let links = G::get_links(unsafe { ptr.as_ref() });
+        if !links.acquire_for_insertion() {
+            // If insertion failed, rebuild object so that it can be freed.
+            // SAFETY: We just called `into_pointer` above.
+            unsafe { G::Wrapped::from_pointer(ptr) };
+            return false;
+        }
+
+        // SAFETY: The links were just acquired, so they are owned by us until published.
+        unsafe { (*links.entry.get()).prev = None };
+        let mut head = self.head.load(Ordering::Relaxed);
+        loop {
+            // SAFETY: The links are still owned by us, as the exchange below failed so far.
+            unsafe { (*links.entry.get()).next = NonNull::new(head) };
+            // The release ordering publishes the links written above to the consumer.
+            match self.head.compare_exchange_weak(
+                head,
+                ptr.as_ptr(),
+                Ordering::Release,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => return head.is_null(),
+                Err(cur) => head = cur,
+            }
+        }
+    }
+
+    /// Removes all entries from the stack and returns them as a list.
+    ///
+    /// The entries are in the order in which they were pushed, that is, the oldest one is at the
+    /// front of the returned list.
+    pub fn take_all(&self) -> List<G> {
+        // The acquire ordering pairs with the release in `push`, so the links of all entries in
+        // the chain are visible.
+        let mut cur = NonNull::new(self.head.swap(ptr::null_mut(), Ordering::Acquire));
+        let mut list = List::new();
+        while let Some(entry) = cur {
+            // SAFETY: The entry was pushed with `into_pointer`, and is now exclusively owned by us
+            // as it was unlinked from the stack by the swap above. The links are owned by us too.
+            cur = unsafe { (*G::get_links(entry.as_ref()).entry.get()).next };
+            // SAFETY: The links were acquired in `push` and are not on any list anymore. Pushing
+            // to the front reverses the stack order back to the push order.
+            unsafe { list.list.push_acquired(entry, true) };
+        }
+        list
+    }
+}
+
+impl<G: GetLinksWrapped> Default for LockFreeStack<G>
+where
+    G::EntryType: Sized,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<G: GetLinksWrapped> Drop for LockFreeStack<G>
+where
+    G::EntryType: Sized,
+{
+    fn drop(&mut self) {
+        drop(self.take_all());
+    }
+}
+
+// SAFETY: The stack owns the wrapped entries, so it can be sent to other threads as long as they
+// can be sent too.
+unsafe impl<G: GetLinksWrapped> Send for LockFreeStack<G>
+where
+    G::EntryType: Sized,
+    G::Wrapped: Send,
+{
+}
+
+// SAFETY: Entries can be pushed and taken through shared references from any thread, which moves
+// the wrapped entries between threads, so they must be `Send`.
+unsafe impl<G: GetLinksWrapped> Sync for LockFreeStack<G>
+where
+    G::EntryType: Sized,
+    G::Wrapped: Send,
+{
+}
+
+#[cfg(all(test, feature = "alloc", not(loom)))]
+mod tests {
+    use std::{sync::Arc, thread, vec::Vec};
+
+    use super::LockFreeStack;
+    use crate::{GetLinks, Links};
+
+    struct Example {
+        inner: usize,
+        links: Links<Self>,
+    }
+
+    impl GetLinks for Example {
+        type EntryType = Self;
+        fn get_links(obj: &Self) -> &Links<Self> {
+            &obj.links
+        }
+    }
+
+    fn new_example(inner: usize) -> Example {
+        Example {
+            inner,
+            links: Links::new(),
+        }
+    }
+
+    #[test]
+    fn test_push_take_all() {
+        let stack = LockFreeStack::<Box<Example>>::new();
+        assert!(stack.is_empty());
+        assert!(stack.take_all().is_empty());
+
+        assert!(stack.push(Box::new(new_example(0))));
+        for n in 1..5 {
+            assert!(!stack.push(Box::new(new_example(n))));
+        }
+        assert!(!stack.is_empty());
+
+        let list = stack.take_all();
+        assert!(stack.is_empty());
+        let values: Vec<_> = list.iter().map(|e| e.inner).collect();
+        assert_eq!(values, [0, 1, 2, 3, 4]);
+        let values: Vec<_> = list.iter().rev().map(|e| e.inner).collect();
+        assert_eq!(values, [4, 3, 2, 1, 0]);
+    }
+
+    #[test]
+    fn test_already_inserted() {
+        let shared = Arc::new(new_example(0));
+        let stack = LockFreeStack::<Arc<Example>>::new();
+        assert!(stack.push(shared.clone()));
+        // Already on the stack, so this is dropped.
+        assert!(!stack.push(shared.clone()));
+        assert_eq!(Arc::strong_count(&shared), 2);
+
+        // The entry stays owned while it moves to the list.
+        let mut list = stack.take_all();
+        assert!(!stack.push(shared.clone()));
+        assert_eq!(Arc::strong_count(&shared), 2);
+        assert!(list.pop_front().is_some());
+        assert!(stack.push(shared.clone()));
+
+        drop(stack);
+        assert_eq!(Arc::strong_count(&shared), 1);
+    }
+
+    #[test]
+    fn test_concurrent_push() {
+        const THREADS: usize = 4;
+        const PER_THREAD: usize = 1000;
+
+        let stack = Arc::new(LockFreeStack::<Box<Example>>::new());
+        let handles: Vec<_> = (0..THREADS)
+            .map(|t| {
+                let stack = stack.clone();
+                thread::spawn(move || {
+                    for i in 0..PER_THREAD {
+                        stack.push(Box::new(new_example(t * PER_THREAD + i)));
+                    }
+                })
+            })
+            .collect();
+
+        let mut seen = Vec::new();
+        for h in handles {
+            h.join().unwrap();
+            seen.extend(stack.take_all().iter().map(|e| e.inner));
+        }
+        seen.extend(stack.take_all().iter().map(|e| e.inner));
+
+        seen.sort_unstable();
+        assert_eq!(seen, (0..THREADS * PER_THREAD).collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,382 @@
+// SPDX-License-Identifier: GPL-2.0
+
+//! Least-recently-used lists and caches.
+//!
+//! An [`LruList`] keeps its entries ordered by recency: used entries are moved to the front in
+//! constant time, and entries are evicted from the back.
+
+#[cfg(feature = "alloc")]
+use alloc::collections::BTreeMap;
+use core::ptr::NonNull;
+
+#[cfg(feature = "alloc")]
+use crate::KeyOf;
+use crate::{
+    linked_list::{GetLinksWrapped, Wrapper},
+    List,
+};
+
+/// A list of wrapped entries ordered from the most to the least recently used.
+///
+/// The list holds at most `capacity` entries: inserting into a full list evicts the least recently
+/// used entry.
+pub struct LruList<G: GetLinksWrapped> {
+    list: List<G>,
+    len: usize,
+    capacity: usize,
+}
+
+impl<G: GetLinksWrapped> LruList<G> {
+    /// Constructs a new empty list that holds up to `capacity` entries.
+    pub const fn new(capacity: usize) -> Self {
+        Self {
+            list: List::new(),
+            len: 0,
+            capacity,
+        }
+    }
+
+    /// Returns the number of entries on the list.
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns the maximum number of entries on the list.
+    pub const fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Returns whether the list is empty.
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns whether the list is full, so that the next insertion evicts an entry.
+    pub const fn is_full(&self) -> bool {
+        self.len >= self.capacity
+    }
+
+    /// Returns an iterator over the entries, from the most to the least recently used.
+    pub fn iter(&self) -> crate::linked_list::Iterator<'_, G> {
+        self.list.iter()
+    }
+
+    /// Adds the given object to the front of the list and returns the pointer it is linked by.
+    fn push_front(&mut self, data: G::Wrapped) -> Option<NonNull<G::EntryType>> {
+        let ptr = data.into_pointer();
+        // SAFETY: We took ownership of the entry, so it is safe to insert it.
+        if unsafe { self.list.list.push_back_internal(ptr, true) } {
+            self.len += 1;
+            Some(ptr)
+        } else {
+            // If insertion failed, rebuild object so that it can be freed.
+            // SAFETY: We just called `into_pointer` above.
+            unsafe { G::Wrapped::from_pointer(ptr) };
+            None
+        }
+    }
+
+    /// Adds the given object to the front of the list as the most recently used entry.
+    ///
+    /// If the list was full, the least recently used entry is evicted and returned. The object is
+    /// dropped if it's already on this (or another) list; this can happen for reference-counted
+    /// objects, so dropping means decrementing the reference count.
+    pub fn insert(&mut self, data: G::Wrapped) -> Option<G::Wrapped> {
+        self.push_front(data)?;
+        if self.len > self.capacity {
+            self.evict()
+        } else {
+            None
+        }
+    }
+
+    /// Marks the given entry as the most recently used one by moving it to the front.
+    ///
+    /// Returns `false` if the entry isn't on any list.
+    ///
+    /// # Safety
+    ///
+    /// Callers must ensure that `data` is either on this list or in no list. It being on another
+    /// list leads to memory unsafety.
+    pub unsafe fn touch(&mut self, data: &G::EntryType) -> bool {
+        // The links stay acquired so that the entry can't be inserted elsewhere while it is
+        // moved.
+        // SAFETY: The safety requirements are the same as `RawList::unlink`.
+        let Some(ptr) = (unsafe { self.list.list.unlink(data) }) else {
+            return false;
+        };
+        // SAFETY: The links are still acquired and the entry was just unlinked.
+        unsafe { self.list.list.push_acquired(ptr, true) };
+        true
+    }
+
+    /// Removes the least recently used entry and returns it.
+    ///
+    /// Returns `None` if the list is empty.
+    pub fn evict(&mut self) -> Option<G::Wrapped> {
+        let data = self.list.pop_back()?;
+        self.len -= 1;
+        Some(data)
+    }
+
+    /// Removes the given entry.
+    ///
+    /// # Safety
+    ///
+    /// Callers must ensure that `data` is either on this list or in no list. It being on another
+    /// list leads to memory unsafety.
+    pub unsafe fn remove(&mut self, data: &G::Wrapped) -> Option<G::Wrapped> {
+        // SAFETY: The safety requirements are the same as `List::remove`.
+        let data = unsafe { self.list.remove(data) }?;
+        self.len -= 1;
+        Some(data)
+    }
+}
+
+/// A cache of wrapped entries indexed by the key returned by [`KeyOf`], which evicts the least
+/// recently used entry when it is full.
+///
+/// Lookups go through a [`BTreeMap`] from keys to the entries of an [`LruList`].
+#[cfg(feature = "alloc")]
+pub struct LruCache<K, G: GetLinksWrapped> {
+    map: BTreeMap<K, NonNull<G::EntryType>>,
+    list: LruList<G>,
+}
+
+#[cfg(feature = "alloc")]
+impl<K, G> LruCache<K, G>
+where
+    K: Ord + Clone,
+    G: GetLinksWrapped,
+    G::EntryType: KeyOf<K>,
+{
+    /// Constructs a new empty cache that holds up to `capacity` entries.
+    pub const fn new(capacity: usize) -> Self {
+        Self {
+            map: BTreeMap::new(),
+            list: LruList::new(capacity),
+        }
+    }
+
+    /// Returns the number of entries in the cache.
+    pub fn len(&self) -> usize {
+        self.list.len()
+    }
+
+    /// Returns the maximum number of entries in the cache.
+    pub fn capacity(&self) -> usize {
+        self.list.capacity()
+    }
+
+    /// Returns whether the cache is empty.
+    pub fn is_empty(&self) -> bool {
+        self.list.is_empty()
+    }
+
+    /// Adds the given object to the cache as the most recently used entry.
+    ///
+    /// Returns the entry it replaced if there was one with the same key, or the least recently
+    /// used entry if the cache was full. The object is dropped if it's already on a list, in which
+    /// case the cache is left unchanged.
+    pub fn insert(&mut self, data: G::Wrapped) -> Option<G::Wrapped> {
+        let key = data.as_ref().key_of().clone();
+        // Keep the pointer from `into_pointer` rather than one derived from `data`, which moving
+        // `data` (e.g. a `Box`) would invalidate.
+        let ptr = data.into_pointer();
+        // Acquire the links before replacing the entry with the same key, so that it is kept if
+        // the object can't be inserted.
+        // SAFETY: We took ownership of the entry, so it is valid.
+        if !G::get_links(unsafe { ptr.as_ref() }).acquire_for_insertion() {
+            // If insertion failed, rebuild object so that it can be freed.
+            // SAFETY: We just called `into_pointer` above.
+            unsafe { G::Wrapped::from_pointer(ptr) };
+            return None;
+        }
+        let replaced = self.remove(&key);
+        // SAFETY: The links were just acquired, and we own the entry until it is removed.
+        unsafe { self.list.list.list.push_acquired(ptr, true) };
+        self.list.len += 1;
+        self.map.insert(key, ptr);
+        if self.list.len() > self.list.capacity() {
+            return self.evict();
+        }
+        replaced
+    }
+
+    /// Returns the entry with the given key, marking it as the most recently used one.
+    pub fn get(&mut self, key: &K) -> Option<&G::EntryType> {
+        let ptr = *self.map.get(key)?;
+        // SAFETY: Entries in the map are on the list, so they are valid.
+        let entry = unsafe { ptr.as_ref() };
+        // SAFETY: Entries in the map are on the list.
+        unsafe { self.list.touch(entry) };
+        Some(entry)
+    }
+
+    /// Returns the entry with the given key without marking it as used.
+    pub fn peek(&self, key: &K) -> Option<&G::EntryType> {
+        // SAFETY: Entries in the map are on the list, so they are valid.
+        self.map.get(key).map(|ptr| unsafe { ptr.as_ref() })
+    }
+
+    /// Removes the entry with the given key and returns it.
+    pub fn remove(&mut self, key: &K) -> Option<G::Wrapped> {
+        let ptr = self.map.remove(key)?;
+        // SAFETY: Entries in the map are on the list, so they are valid.
+        let entry = unsafe { ptr.as_ref() };
+        // SAFETY: Entries in the map are on the list.
+        let ptr = unsafe { self.list.list.list.remove_internal(entry) }?;
+        self.list.len -= 1;
+        // SAFETY: Elements on the list were inserted after a call to `into_pointer`.
+        Some(unsafe { G::Wrapped::from_pointer(ptr) })
+    }
+
+    /// Removes the least recently used entry and returns it.
+    ///
+    /// Returns `None` if the cache is empty.
+    pub fn evict(&mut self) -> Option<G::Wrapped> {
+        let data = self.list.evict()?;
+        self.map.remove(data.as_ref().key_of());
+        Some(data)
+    }
+}
+
+// SAFETY: The map only holds keys and pointers to the entries owned by the list, so the cache can
+// be sent to other threads when the keys and the wrapped entries can.
+#[cfg(feature = "alloc")]
+unsafe impl<K: Send, G: GetLinksWrapped> Send for LruCache<K, G> where G::Wrapped: Send {}
+
+// SAFETY: Shared references to the cache only hand out shared references to its keys and entries,
+// which is as much as shared references to the keys and the wrappers allow.
+#[cfg(feature = "alloc")]
+unsafe impl<K: Sync, G: GetLinksWrapped> Sync for LruCache<K, G> where G::Wrapped: Sync {}
+
+#[cfg(all(test, feature = "alloc", not(loom)))]
+mod tests {
+    use std::{boxed::Box, sync::Arc, vec::Vec};
+
+    use super::LruCache;
+    use super::LruList;
+    use crate::{GetLinks, KeyOf, Links};
+
+    struct Page {
+        index: usize,
+        links: Links<Self>,
+    }
+
+    impl GetLinks for Page {
+        type EntryType = Self;
+        fn get_links(obj: &Self) -> &Links<Self> {
+            &obj.links
+        }
+    }
+
+    impl KeyOf<usize> for Page {
+        fn key_of(&self) -> &usize {
+            &self.index
+        }
+    }
+
+    fn new_page(index: usize) -> Arc<Page> {
+        Arc::new(Page {
+            index,
+            links: Links::new(),
+        })
+    }
+
+    fn indices(lru: &LruList<Arc<Page>>) -> Vec<usize> {
+        lru.iter().map(|p| p.index).collect()
+    }
+
+    #[test]
+    fn test_lru_list() {
+        let pages: Vec<_> = (0..4).map(new_page).collect();
+        let mut lru = LruList::<Arc<Page>>::new(3);
+        for p in &pages[..3] {
+            assert!(lru.insert(p.clone()).is_none());
+        }
+        assert!(lru.is_full());
+        assert_eq!(indices(&lru), [2, 1, 0]);
+
+        // SAFETY: The page is on this list.
+        assert!(unsafe { lru.touch(&pages[0]) });
+        assert_eq!(indices(&lru), [0, 2, 1]);
+        // SAFETY: The page is in no list.
+        assert!(!unsafe { lru.touch(&pages[3]) });
+
+        assert_eq!(lru.insert(pages[3].clone()).unwrap().index, 1);
+        assert_eq!(indices(&lru), [3, 0, 2]);
+        assert_eq!(lru.len(), 3);
+
+        // SAFETY: The page is on this list.
+        assert!(unsafe { lru.remove(&pages[0]) }.is_some());
+        assert_eq!(lru.evict().unwrap().index, 2);
+        assert_eq!(lru.len(), 1);
+        drop(lru);
+        assert!(pages.iter().all(|p| Arc::strong_count(p) == 1));
+    }
+
+    #[test]
+    fn test_lru_cache() {
+        let mut cache = LruCache::<usize, Arc<Page>>::new(2);
+        assert!(cache.insert(new_page(1)).is_none());
+        assert!(cache.insert(new_page(2)).is_none());
+
+        // Using 1 makes 2 the least recently used entry.
+        assert_eq!(cache.get(&1).unwrap().index, 1);
+        assert_eq!(cache.insert(new_page(3)).unwrap().index, 2);
+        assert!(cache.peek(&2).is_none());
+
+        // Replacing an entry doesn't evict another one.
+        let old = cache.get(&3).map(|p| p as *const Page).unwrap();
+        let replaced = cache.insert(new_page(3)).unwrap();
+        assert!(core::ptr::eq(&*replaced, old));
+        assert_eq!(cache.len(), 2);
+
+        assert_eq!(cache.remove(&1).unwrap().index, 1);
+        assert!(cache.remove(&1).is_none());
+        assert_eq!(cache.evict().unwrap().index, 3);
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn test_lru_cache_insert_linked() {
+        let mut cache = LruCache::<usize, Arc<Page>>::new(2);
+        assert!(cache.insert(new_page(1)).is_none());
+
+        // A page with the same key that is on another list is dropped, and the cached page kept.
+        let page = new_page(1);
+        let mut other = LruList::<Arc<Page>>::new(1);
+        assert!(other.insert(page.clone()).is_none());
+        assert!(cache.insert(page.clone()).is_none());
+        assert_eq!(Arc::strong_count(&page), 2);
+        let cached = cache.peek(&1).unwrap();
+        assert!(!core::ptr::eq(cached, &*page));
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[test]
+    fn test_lru_cache_boxed() {
+        let mut cache = LruCache::<usize, Box<Page>>::new(2);
+        for index in 1..=2 {
+            let page = Box::new(Page {
+                index,
+                links: Links::new(),
+            });
+            assert!(cache.insert(page).is_none());
+        }
+
+        assert_eq!(cache.get(&1).unwrap().index, 1);
+        assert_eq!(cache.peek(&2).unwrap().index, 2);
+        let page = Box::new(Page {
+            index: 3,
+            links: Links::new(),
+        });
+        assert_eq!(cache.insert(page).unwrap().index, 2);
+
+        assert_eq!(cache.remove(&1).unwrap().index, 1);
+        assert_eq!(cache.get(&3).unwrap().index, 3);
+        assert_eq!(cache.evict().unwrap().index, 3);
+        assert!(cache.is_empty());
+    }
+}
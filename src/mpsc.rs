@@ -0,0 +1,498 @@
+// SPDX-License-Identifier: GPL-2.0
+
+//! Lock-free multi-producer single-consumer queues.
+//!
+//! Based on Dmitry Vyukov's intrusive MPSC node-based queue: producers never wait for each other
+//! or for the consumer, and the consumer never waits for producers.
+
+#[cfg(feature = "alloc")]
+use alloc::{boxed::Box, sync::Arc};
+use core::{pin::Pin, ptr, ptr::NonNull};
+
+use crate::{
+    linked_list::Wrapper,
+    sync::{loom_const_fn, AtomicBool, AtomicPtr, Ordering, UnsafeCell},
+};
+
+/// A descriptor of queue elements.
+///
+/// It describes the type of queue elements and provides a function to determine how to get the
+/// links to be used on a queue.
+pub trait GetAtomicLinks {
+    /// The type of the entries in the queue.
+    type EntryType: ?Sized;
+
+    /// Returns the links to be used when linking an entry within a queue.
+    fn get_links(data: &Self::EntryType) -> &AtomicLinks<Self::EntryType>;
+}
+
+/// A descriptor of wrapped queue elements.
+pub trait GetAtomicLinksWrapped: GetAtomicLinks {
+    /// Specifies which wrapper (e.g., `Box` and `Arc`) wraps the queue entries.
+    type Wrapped: Wrapper<Self::EntryType>;
+}
+
+#[cfg(feature = "alloc")]
+impl<T: ?Sized> GetAtomicLinksWrapped for Box<T>
+where
+    Box<T>: GetAtomicLinks,
+{
+    type Wrapped = Box<<Box<T> as GetAtomicLinks>::EntryType>;
+}
+
+#[cfg(feature = "alloc")]
+impl<T: GetAtomicLinks + ?Sized> GetAtomicLinks for Box<T> {
+    type EntryType = T::EntryType;
+
+    #[inline]
+    fn get_links(data: &Self::EntryType) -> &AtomicLinks<Self::EntryType> {
+        <T as GetAtomicLinks>::get_links(data)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T: ?Sized> GetAtomicLinksWrapped for Arc<T>
+where
+    Arc<T>: GetAtomicLinks,
+{
+    type Wrapped = Arc<<Arc<T> as GetAtomicLinks>::EntryType>;
+}
+
+#[cfg(feature = "alloc")]
+impl<T: GetAtomicLinks + ?Sized> GetAtomicLinks for Arc<T> {
+    type EntryType = T::EntryType;
+
+    #[inline]
+    fn get_links(data: &Self::EntryType) -> &AtomicLinks<Self::EntryType> {
+        <T as GetAtomicLinks>::get_links(data)
+    }
+}
+
+impl<T: GetAtomicLinks + ?Sized> GetAtomicLinks for &T {
+    type EntryType = T::EntryType;
+
+    #[inline]
+    fn get_links(data: &Self::EntryType) -> &AtomicLinks<Self::EntryType> {
+        <T as GetAtomicLinks>::get_links(data)
+    }
+}
+
+impl<'a, T: ?Sized> GetAtomicLinksWrapped for &'a T
+where
+    &'a T: GetAtomicLinks,
+{
+    type Wrapped = &'a <&'a T as GetAtomicLinks>::EntryType;
+}
+
+impl<P: GetAtomicLinks> GetAtomicLinks for Pin<P> {
+    type EntryType = P::EntryType;
+
+    #[inline]
+    fn get_links(data: &Self::EntryType) -> &AtomicLinks<Self::EntryType> {
+        <P as GetAtomicLinks>::get_links(data)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T: ?Sized> GetAtomicLinksWrapped for Pin<Box<T>>
+where
+    Box<T>: GetAtomicLinks,
+{
+    type Wrapped = Pin<Box<<Box<T> as GetAtomicLinks>::EntryType>>;
+}
+
+#[cfg(feature = "alloc")]
+impl<T: ?Sized> GetAtomicLinksWrapped for Pin<Arc<T>>
+where
+    Arc<T>: GetAtomicLinks,
+{
+    type Wrapped = Pin<Arc<<Arc<T> as GetAtomicLinks>::EntryType>>;
+}
+
+impl<'a, T: ?Sized> GetAtomicLinksWrapped for Pin<&'a T>
+where
+    &'a T: GetAtomicLinks,
+{
+    type Wrapped = Pin<&'a <&'a T as GetAtomicLinks>::EntryType>;
+}
+
+/// The links used to link an object on a lock-free queue.
+///
+/// Instances of this type are usually embedded in structures and returned in calls to
+/// [`GetAtomicLinks::get_links`].
+pub struct AtomicLinks<T: ?Sized> {
+    inserted: AtomicBool,
+    next: AtomicPtr<AtomicLinks<T>>,
+    entry: UnsafeCell<Option<NonNull<T>>>,
+}
+
+// SAFETY: `AtomicLinks` can be safely sent to other threads but we restrict it to being `Send`
+// only when the queue entries it points to are also `Send`.
+unsafe impl<T: ?Sized> Send for AtomicLinks<T> {}
+
+// SAFETY: `AtomicLinks` is usable from other threads via references but we restrict it to being
+// `Sync` only when the queue entries it points to are also `Sync`.
+unsafe impl<T: ?Sized> Sync for AtomicLinks<T> {}
+
+impl<T: ?Sized> AtomicLinks<T> {
+    loom_const_fn! {
+        /// Constructs a new [`AtomicLinks`] instance that isn't inserted on any queues yet.
+        pub fn new() -> Self {
+            Self {
+                inserted: AtomicBool::new(false),
+                next: AtomicPtr::new(ptr::null_mut()),
+                entry: UnsafeCell::new(None),
+            }
+        }
+    }
+
+    fn acquire_for_insertion(&self) -> bool {
+        self.inserted
+            .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_ok()
+    }
+
+    fn release_after_removal(&self) {
+        self.inserted.store(false, Ordering::Release);
+    }
+}
+
+impl<T: ?Sized> Default for AtomicLinks<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A lock-free, intrusive, multi-producer single-consumer FIFO queue.
+///
+/// Any number of threads can [`push`](MpscQueue::push) concurrently through shared references,
+/// while a single consumer [`pop`](MpscQueue::pop)s entries in the order in which they were
+/// pushed.
+///
+/// The queue is not linearisable: while a producer is in the middle of a push, the consumer may
+/// not see the entries pushed after it until that push completes.
+///
+/// # Invariants
+///
+/// The queue keeps a stub link of its own, which is referred to with the address-less pointer
+/// `STUB` so that the queue can be moved while it is not shared. `head` is the most recently
+/// pushed link, `tail` is the oldest one, and the `next` pointers lead from `tail` to `head`
+/// (except when a push is in progress). The links of objects added to a queue are owned by it.
+pub struct MpscQueue<G: GetAtomicLinksWrapped> {
+    head: AtomicPtr<AtomicLinks<G::EntryType>>,
+    tail: AtomicPtr<AtomicLinks<G::EntryType>>,
+    stub: AtomicLinks<G::EntryType>,
+}
+
+impl<G: GetAtomicLinksWrapped> MpscQueue<G> {
+    /// The pointer that stands for `stub` in the queue. It is never dereferenced.
+    const STUB: *mut AtomicLinks<G::EntryType> = ptr::dangling_mut();
+
+    loom_const_fn! {
+        /// Constructs a new empty queue.
+        pub fn new() -> Self {
+            Self {
+                head: AtomicPtr::new(Self::STUB),
+                tail: AtomicPtr::new(Self::STUB),
+                stub: AtomicLinks::new(),
+            }
+        }
+    }
+
+    /// Returns the links pointed to by `ptr`, which is either `STUB` or points to the links of an
+    /// entry on the queue.
+    ///
+    /// # Safety
+    ///
+    /// If `ptr` is not `STUB`, it must point to the links of an entry on the queue.
+    unsafe fn links(&self, ptr: *mut AtomicLinks<G::EntryType>) -> &AtomicLinks<G::EntryType> {
+        if ptr == Self::STUB {
+            &self.stub
+        } else {
+            // SAFETY: The entry is valid while on the queue.
+            unsafe { &*ptr }
+        }
+    }
+
+    /// Returns whether the queue is empty.
+    ///
+    /// Entries whose push is still in progress may not be accounted for.
+    pub fn is_empty(&self) -> bool {
+        self.tail.load(Ordering::Relaxed) == Self::STUB
+            && self.stub.next.load(Ordering::Acquire).is_null()
+    }
+
+    /// Links `new` at the head of the queue.
+    ///
+    /// # Safety
+    ///
+    /// `new` must be `STUB` or point to the links of a valid entry owned by the queue.
+    unsafe fn push_links(&self, new: *mut AtomicLinks<G::EntryType>) {
+        // SAFETY: `new` is valid by the safety requirements.
+        unsafe { self.links(new) }
+            .next
+            .store(ptr::null_mut(), Ordering::Relaxed);
+        // The acquire ordering makes the links of `prev` accessible, and the release ordering
+        // publishes `new` to the next producer.
+        let prev = self.head.swap(new, Ordering::AcqRel);
+        // The release ordering publishes `new` to the consumer.
+        // SAFETY: `prev` was the head of the queue, so it is still on the queue until the
+        // consumer sees its `next` pointer, which is only set below.
+        unsafe { self.links(prev) }
+            .next
+            .store(new, Ordering::Release);
+    }
+
+    /// Adds the given object to the end (back) of the queue.
+    ///
+    /// It is dropped if it's already on this (or another) queue; this can happen for
+    /// reference-counted objects, so dropping means decrementing the reference count.
+    pub fn push(&self, data: G::Wrapped) {
+        let ptr = data.into_pointer();
+        // SAFETY: We took ownership of the entry, so it is valid.
+        let links = G::get_links(unsafe { ptr.as_ref() });
+        if !links.acquire_for_insertion() {
+            // If insertion failed, rebuild object so that it can be freed.
+            // SAFETY: We just called `into_pointer` above.
+            unsafe { G::Wrapped::from_pointer(ptr) };
+            return;
+        }
+
+        // SAFETY: The links were just acquired, so they are owned by us until published.
+        unsafe { *links.entry.get() = Some(ptr) };
+        // SAFETY: The links belong to an entry owned by the queue.
+        unsafe { self.push_links(links as *const _ as *mut _) };
+    }
+
+    /// Removes the entry whose links are pointed to by `links` and returns it.
+    ///
+    /// # Safety
+    ///
+    /// `links` must point to the links of an entry that was just unlinked from the queue.
+    unsafe fn take(links: *mut AtomicLinks<G::EntryType>) -> G::Wrapped {
+        // SAFETY: The entry is valid until we give its ownership back.
+        let links = unsafe { &*links };
+        // SAFETY: The links are owned by the consumer, and the entry was set on push.
+        let ptr = unsafe { (*links.entry.get()).take().unwrap() };
+        links.release_after_removal();
+        // SAFETY: Elements on the queue were inserted after a call to `into_pointer`.
+        unsafe { G::Wrapped::from_pointer(ptr) }
+    }
+
+    /// Removes the element at the front of the queue and returns it.
+    ///
+    /// Returns `None` if the queue is empty, or if the next element is being pushed concurrently.
+    pub fn pop(&mut self) -> Option<G::Wrapped> {
+        // SAFETY: We have exclusive access to the queue, so there are no other consumers.
+        unsafe { self.pop_concurrent() }
+    }
+
+    /// Removes the element at the front of the queue and returns it, while producers may be
+    /// pushing concurrently.
+    ///
+    /// Returns `None` if the queue is empty, or if the next element is being pushed concurrently.
+    ///
+    /// # Safety
+    ///
+    /// Callers must ensure that there is a single consumer, that is, that this is never called
+    /// concurrently with itself.
+    pub unsafe fn pop_concurrent(&self) -> Option<G::Wrapped> {
+        let mut tail = self.tail.load(Ordering::Relaxed);
+        // SAFETY: The tail is on the queue and only the consumer unlinks it.
+        let mut next = unsafe { self.links(tail) }.next.load(Ordering::Acquire);
+
+        if tail == Self::STUB {
+            if next.is_null() {
+                return None;
+            }
+            // Skip the stub.
+            self.tail.store(next, Ordering::Relaxed);
+            tail = next;
+            // SAFETY: `next` was linked after the stub, so it is on the queue.
+            next = unsafe { self.links(next) }.next.load(Ordering::Acquire);
+        }
+
+        if !next.is_null() {
+            self.tail.store(next, Ordering::Relaxed);
+            // SAFETY: `tail` is not `STUB` and is no longer reachable from the queue.
+            return Some(unsafe { Self::take(tail) });
+        }
+
+        if tail != self.head.load(Ordering::Acquire) {
+            // A producer is in the middle of pushing after `tail`.
+            return None;
+        }
+
+        // `tail` is the last entry, so push the stub behind it to be able to unlink it.
+        // SAFETY: `STUB` can always be pushed, as the consumer just checked it isn't queued.
+        unsafe { self.push_links(Self::STUB) };
+        // SAFETY: The tail is on the queue.
+        next = unsafe { self.links(tail) }.next.load(Ordering::Acquire);
+        if !next.is_null() {
+            self.tail.store(next, Ordering::Relaxed);
+            // SAFETY: `tail` is not `STUB` and is no longer reachable from the queue.
+            return Some(unsafe { Self::take(tail) });
+        }
+        None
+    }
+}
+
+impl<G: GetAtomicLinksWrapped> Default for MpscQueue<G> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<G: GetAtomicLinksWrapped> Drop for MpscQueue<G> {
+    fn drop(&mut self) {
+        while self.pop().is_some() {}
+    }
+}
+
+// SAFETY: The queue owns the wrapped entries, so it can be sent to other threads as long as they
+// can be sent too.
+unsafe impl<G: GetAtomicLinksWrapped> Send for MpscQueue<G> where G::Wrapped: Send {}
+
+// SAFETY: Entries can be pushed and popped through shared references from any thread, which moves
+// the wrapped entries between threads, so they must be `Send`.
+unsafe impl<G: GetAtomicLinksWrapped> Sync for MpscQueue<G> where G::Wrapped: Send {}
+
+#[cfg(all(test, feature = "alloc"))]
+mod tests {
+    use super::{AtomicLinks, GetAtomicLinks, MpscQueue};
+
+    struct Example {
+        inner: usize,
+        links: AtomicLinks<Self>,
+    }
+
+    impl GetAtomicLinks for Example {
+        type EntryType = Self;
+        fn get_links(obj: &Self) -> &AtomicLinks<Self> {
+            &obj.links
+        }
+    }
+
+    fn new_example(inner: usize) -> Example {
+        Example {
+            inner,
+            links: AtomicLinks::new(),
+        }
+    }
+
+    #[cfg(not(loom))]
+    #[test]
+    fn test_push_pop() {
+        let mut queue = MpscQueue::<Box<Example>>::new();
+        assert!(queue.is_empty());
+        assert!(queue.pop().is_none());
+
+        for round in 0..3 {
+            for n in 0..5 {
+                queue.push(Box::new(new_example(round * 10 + n)));
+            }
+            assert!(!queue.is_empty());
+            for n in 0..5 {
+                assert_eq!(queue.pop().unwrap().inner, round * 10 + n);
+            }
+            assert!(queue.pop().is_none());
+            assert!(queue.is_empty());
+        }
+
+        // Dropping a non-empty queue drops its entries.
+        queue.push(Box::new(new_example(0)));
+    }
+
+    #[cfg(not(loom))]
+    #[test]
+    fn test_already_inserted() {
+        use std::sync::Arc;
+
+        let shared = Arc::new(new_example(0));
+        let mut queue = MpscQueue::<Arc<Example>>::new();
+        queue.push(shared.clone());
+        // Already on the queue, so this is dropped.
+        queue.push(shared.clone());
+        assert_eq!(Arc::strong_count(&shared), 2);
+
+        let popped = queue.pop().unwrap();
+        assert!(queue.pop().is_none());
+        queue.push(popped);
+        drop(queue);
+        assert_eq!(Arc::strong_count(&shared), 1);
+    }
+
+    #[cfg(not(loom))]
+    #[test]
+    fn test_concurrent_push_pop() {
+        use std::{sync::Arc, thread, vec::Vec};
+
+        const THREADS: usize = 4;
+        const PER_THREAD: usize = 10_000;
+
+        let queue = Arc::new(MpscQueue::<Box<Example>>::new());
+        let producers: Vec<_> = (0..THREADS)
+            .map(|t| {
+                let queue = queue.clone();
+                thread::spawn(move || {
+                    for i in 0..PER_THREAD {
+                        queue.push(Box::new(new_example(t * PER_THREAD + i)));
+                    }
+                })
+            })
+            .collect();
+
+        let mut last = [None; THREADS];
+        let mut count = 0;
+        while count < THREADS * PER_THREAD {
+            // SAFETY: This is the only consumer.
+            let Some(e) = (unsafe { queue.pop_concurrent() }) else {
+                thread::yield_now();
+                continue;
+            };
+            // Entries from the same producer come out in order.
+            let (t, i) = (e.inner / PER_THREAD, e.inner % PER_THREAD);
+            assert!(last[t].is_none_or(|l| l < i));
+            last[t] = Some(i);
+            count += 1;
+        }
+
+        for p in producers {
+            p.join().unwrap();
+        }
+        assert!(queue.is_empty());
+    }
+
+    #[cfg(loom)]
+    #[test]
+    fn loom_concurrent_push_pop() {
+        use loom::{sync::Arc, thread};
+
+        loom::model(|| {
+            let queue = Arc::new(MpscQueue::<Box<Example>>::new());
+            let producers: Vec<_> = (0..2)
+                .map(|t| {
+                    let queue = queue.clone();
+                    thread::spawn(move || queue.push(Box::new(new_example(t))))
+                })
+                .collect();
+
+            let mut seen = Vec::new();
+            // SAFETY: This is the only consumer.
+            if let Some(e) = unsafe { queue.pop_concurrent() } {
+                seen.push(e.inner);
+            }
+            for p in producers {
+                p.join().unwrap();
+            }
+            // SAFETY: This is the only consumer.
+            while let Some(e) = unsafe { queue.pop_concurrent() } {
+                seen.push(e.inner);
+            }
+
+            seen.sort_unstable();
+            assert_eq!(seen, [0, 1]);
+            assert!(queue.is_empty());
+        });
+    }
+}
@@ -0,0 +1,256 @@
+// SPDX-License-Identifier: GPL-2.0
+
+//! Offset-based list adapters.
+//!
+//! These mirror the C idiom of embedding a `list_head` in a structure and recovering the
+//! containing structure with `container_of`.
+
+use core::{marker::PhantomData, ptr::NonNull};
+
+use crate::{GetLinks, Links};
+
+/// A descriptor of list elements whose links are embedded at a fixed offset.
+///
+/// Unlike [`GetLinks`], which maps an entry to its links through a function, this describes the
+/// location of the links as a byte offset within the entry, so the entry can also be recovered
+/// from its links (see [`OffsetAdapter::container_of`]).
+///
+/// Use [`OffsetAdapter`] to obtain a [`GetLinks`] implementation that can be used with lists.
+///
+/// # Safety
+///
+/// Implementers must ensure that `LINKS_OFFSET` is the offset, in bytes, of a field of type
+/// [`Links<Self::EntryType>`] within `Self::EntryType`. It should be computed with
+/// [`core::mem::offset_of!`].
+pub unsafe trait GetLinksOffset {
+    /// The type of the entries in the list.
+    type EntryType;
+
+    /// The offset of the links within an entry.
+    const LINKS_OFFSET: usize;
+}
+
+/// An adapter that implements [`GetLinks`] for a [`GetLinksOffset`] descriptor.
+///
+/// # Examples
+///
+/// ```
+/// use core::mem::offset_of;
+/// use linked_list_r4l::{GetLinksOffset, Links, List, OffsetAdapter};
+///
+/// struct Node {
+///     value: usize,
+///     links: Links<Node>,
+/// }
+///
+/// struct NodeLinks;
+///
+/// // SAFETY: `links` is a `Links<Node>` field of `Node`.
+/// unsafe impl GetLinksOffset for NodeLinks {
+///     type EntryType = Node;
+///     const LINKS_OFFSET: usize = offset_of!(Node, links);
+/// }
+///
+/// let node1 = Node { value: 1, links: Links::new() };
+/// let node2 = Node { value: 2, links: Links::new() };
+/// let mut list = List::<&OffsetAdapter<NodeLinks>>::new();
+/// list.push_back(&node1);
+/// list.push_back(&node2);
+///
+/// let values: Vec<_> = list.iter().map(|n| n.value).collect();
+/// assert_eq!(values, [1, 2]);
+/// ```
+pub struct OffsetAdapter<A: ?Sized>(PhantomData<A>);
+
+impl<A: GetLinksOffset + ?Sized> OffsetAdapter<A> {
+    /// Returns a pointer to the links embedded in the entry pointed to by `data`.
+    ///
+    /// The pointer is computed with address arithmetic only, `data` is not dereferenced.
+    #[inline]
+    pub const fn links_of(data: NonNull<A::EntryType>) -> NonNull<Links<A::EntryType>> {
+        // SAFETY: Adding the offset to a non-null pointer to the entry yields a pointer into the
+        // same object, which is non-null as well.
+        unsafe { data.byte_add(A::LINKS_OFFSET).cast() }
+    }
+
+    /// Returns a pointer to the entry that contains the links pointed to by `links`.
+    ///
+    /// # Safety
+    ///
+    /// Callers must ensure that `links` points to the links embedded in an entry of type
+    /// `A::EntryType`. The returned pointer may only be dereferenced if `links` was derived from a
+    /// pointer to the whole entry, rather than from a reference to the links.
+    #[inline]
+    pub const unsafe fn container_of(links: NonNull<Links<A::EntryType>>) -> NonNull<A::EntryType> {
+        // SAFETY: By the safety requirements, subtracting the offset yields a pointer to the
+        // start of the containing entry.
+        unsafe { links.byte_sub(A::LINKS_OFFSET).cast() }
+    }
+}
+
+impl<A: GetLinksOffset + ?Sized> GetLinks for OffsetAdapter<A> {
+    type EntryType = A::EntryType;
+
+    #[inline]
+    fn get_links(data: &Self::EntryType) -> &Links<Self::EntryType> {
+        // SAFETY: By the safety requirements of `GetLinksOffset`, the links live within `data`
+        // at `LINKS_OFFSET`, so they are valid for as long as `data` is.
+        unsafe { Self::links_of(NonNull::from(data)).as_ref() }
+    }
+}
+
+/// Returns a pointer to the structure of type `$type` that contains the field `$field`, given a
+/// pointer `$ptr` to that field.
+///
+/// The computation does not dereference any pointer, but using the result is only valid if
+/// `$ptr` actually points to the `$field` field of a `$type`, and was derived from a pointer to
+/// the whole `$type` rather than from a reference to the field, which only grants access to the
+/// field. Lists keep the pointers to entries added with [`RawList::push_back_ptr`] as is, so the
+/// ones they return can be used here.
+///
+/// [`RawList::push_back_ptr`]: crate::RawList::push_back_ptr
+///
+/// # Examples
+///
+/// ```
+/// use linked_list_r4l::{container_of, Links};
+///
+/// struct Node {
+///     value: usize,
+///     links: Links<Node>,
+/// }
+///
+/// let node = Node { value: 7, links: Links::new() };
+/// let node_ptr: *const Node = &node;
+/// // SAFETY: `node_ptr` points to `node`, which is alive.
+/// let links = unsafe { &raw const (*node_ptr).links };
+/// let recovered = container_of!(links, Node, links);
+/// assert!(core::ptr::eq(recovered, &node));
+/// // SAFETY: `links` points to the `links` field of `node`, which is still alive.
+/// assert_eq!(unsafe { (*recovered).value }, 7);
+/// ```
+#[macro_export]
+macro_rules! container_of {
+    ($ptr:expr, $type:ty, $($field:tt)*) => {{
+        let ptr = $ptr as *const _ as *const u8;
+        let offset: usize = ::core::mem::offset_of!($type, $($field)*);
+        ptr.wrapping_sub(offset) as *const $type
+    }};
+}
+
+#[cfg(all(test, feature = "alloc", not(loom)))]
+mod tests {
+    use core::{mem::offset_of, ptr::NonNull};
+
+    use super::{GetLinksOffset, OffsetAdapter};
+    use crate::{GetLinks, Links, List, RawList};
+
+    /// A type-erased header shared by several container types.
+    struct Header {
+        kind: u8,
+        links: Links<Header>,
+    }
+
+    struct HeaderLinks;
+
+    // SAFETY: `links` is a `Links<Header>` field of `Header`.
+    unsafe impl GetLinksOffset for HeaderLinks {
+        type EntryType = Header;
+        const LINKS_OFFSET: usize = offset_of!(Header, links);
+    }
+
+    struct Foo {
+        value: u32,
+        header: Header,
+    }
+
+    struct Bar {
+        name: &'static str,
+        header: Header,
+    }
+
+    #[test]
+    fn test_links_offset() {
+        let header = Header {
+            kind: 0,
+            links: Links::new(),
+        };
+        let links = <OffsetAdapter<HeaderLinks> as GetLinks>::get_links(&header);
+        assert!(core::ptr::eq(links, &header.links));
+
+        let ptr = OffsetAdapter::<HeaderLinks>::links_of(NonNull::from(&header));
+        // SAFETY: `ptr` points to the links of `header`.
+        let entry = unsafe { OffsetAdapter::<HeaderLinks>::container_of(ptr) };
+        assert!(core::ptr::eq(entry.as_ptr(), &header));
+    }
+
+    #[test]
+    fn test_type_erased_list() {
+        let foo = Foo {
+            value: 42,
+            header: Header {
+                kind: 0,
+                links: Links::new(),
+            },
+        };
+        let bar = Bar {
+            name: "bar",
+            header: Header {
+                kind: 1,
+                links: Links::new(),
+            },
+        };
+        let foo_ptr: *const Foo = &foo;
+        let bar_ptr: *const Bar = &bar;
+        let mut list = RawList::<OffsetAdapter<HeaderLinks>>::new();
+
+        // SAFETY: Both entries outlive the list and are not moved while on it. The pointers to
+        // the headers are derived from pointers to their containers, so that the containers can
+        // be recovered from them.
+        unsafe {
+            assert!(list.push_back_ptr(NonNull::new_unchecked(
+                (&raw const (*foo_ptr).header).cast_mut()
+            )));
+            assert!(list.push_back_ptr(NonNull::new_unchecked(
+                (&raw const (*bar_ptr).header).cast_mut()
+            )));
+        }
+
+        let mut seen = 0;
+        while let Some(header) = list.pop_front() {
+            // SAFETY: The header is alive, as it is embedded in `foo` or `bar`.
+            match unsafe { header.as_ref() }.kind {
+                0 => {
+                    let foo = container_of!(header.as_ptr(), Foo, header);
+                    assert!(core::ptr::eq(foo, foo_ptr));
+                    // SAFETY: The header is embedded in `foo`, whose provenance it kept.
+                    assert_eq!(unsafe { (*foo).value }, 42);
+                }
+                _ => {
+                    let bar = container_of!(header.as_ptr(), Bar, header);
+                    assert!(core::ptr::eq(bar, bar_ptr));
+                    // SAFETY: The header is embedded in `bar`, whose provenance it kept.
+                    assert_eq!(unsafe { (*bar).name }, "bar");
+                }
+            }
+            seen += 1;
+        }
+        assert_eq!(seen, 2);
+        assert!(list.is_empty());
+    }
+
+    #[test]
+    fn test_wrapped_list() {
+        let mut list = List::<Box<OffsetAdapter<HeaderLinks>>>::new();
+        for kind in 0..4 {
+            list.push_back(Box::new(Header {
+                kind,
+                links: Links::new(),
+            }));
+        }
+        for (i, e) in list.iter().enumerate() {
+            assert_eq!(e.kind as usize, i);
+        }
+        assert_eq!(list.pop_front().unwrap().kind, 0);
+    }
+}
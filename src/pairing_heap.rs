@@ -0,0 +1,618 @@
+// SPDX-License-Identifier: GPL-2.0
+
+//! Intrusive pairing heaps.
+//!
+//! A min-heap of wrapped entries ordered by their [`Ord`] implementation, for things like timer
+//! deadlines that need cheap insertion and removal of the earliest entry. Entries use their own
+//! [`HeapLinks`], and the same wrappers as [`List`](crate::List).
+
+#[cfg(feature = "alloc")]
+use alloc::{boxed::Box, sync::Arc};
+use core::{
+    pin::Pin,
+    ptr::{self, NonNull},
+};
+
+use crate::{
+    linked_list::Wrapper,
+    sync::{loom_const_fn, AtomicBool, Ordering, UnsafeCell},
+};
+
+/// A descriptor of heap elements.
+///
+/// It describes the type of heap elements and provides a function to determine how to get the
+/// links to be used on a heap.
+pub trait GetHeapLinks {
+    /// The type of the entries in the heap.
+    type EntryType: ?Sized;
+
+    /// Returns the links to be used when linking an entry within a heap.
+    fn get_links(data: &Self::EntryType) -> &HeapLinks<Self::EntryType>;
+}
+
+/// A descriptor of wrapped heap elements.
+pub trait GetHeapLinksWrapped: GetHeapLinks {
+    /// Specifies which wrapper (e.g., `Box` and `Arc`) wraps the heap entries.
+    type Wrapped: Wrapper<Self::EntryType>;
+}
+
+#[cfg(feature = "alloc")]
+impl<T: ?Sized> GetHeapLinksWrapped for Box<T>
+where
+    Box<T>: GetHeapLinks,
+{
+    type Wrapped = Box<<Box<T> as GetHeapLinks>::EntryType>;
+}
+
+#[cfg(feature = "alloc")]
+impl<T: GetHeapLinks + ?Sized> GetHeapLinks for Box<T> {
+    type EntryType = T::EntryType;
+
+    #[inline]
+    fn get_links(data: &Self::EntryType) -> &HeapLinks<Self::EntryType> {
+        <T as GetHeapLinks>::get_links(data)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T: ?Sized> GetHeapLinksWrapped for Arc<T>
+where
+    Arc<T>: GetHeapLinks,
+{
+    type Wrapped = Arc<<Arc<T> as GetHeapLinks>::EntryType>;
+}
+
+#[cfg(feature = "alloc")]
+impl<T: GetHeapLinks + ?Sized> GetHeapLinks for Arc<T> {
+    type EntryType = T::EntryType;
+
+    #[inline]
+    fn get_links(data: &Self::EntryType) -> &HeapLinks<Self::EntryType> {
+        <T as GetHeapLinks>::get_links(data)
+    }
+}
+
+impl<T: GetHeapLinks + ?Sized> GetHeapLinks for &T {
+    type EntryType = T::EntryType;
+
+    #[inline]
+    fn get_links(data: &Self::EntryType) -> &HeapLinks<Self::EntryType> {
+        <T as GetHeapLinks>::get_links(data)
+    }
+}
+
+impl<'a, T: ?Sized> GetHeapLinksWrapped for &'a T
+where
+    &'a T: GetHeapLinks,
+{
+    type Wrapped = &'a <&'a T as GetHeapLinks>::EntryType;
+}
+
+impl<P: GetHeapLinks> GetHeapLinks for Pin<P> {
+    type EntryType = P::EntryType;
+
+    #[inline]
+    fn get_links(data: &Self::EntryType) -> &HeapLinks<Self::EntryType> {
+        <P as GetHeapLinks>::get_links(data)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T: ?Sized> GetHeapLinksWrapped for Pin<Box<T>>
+where
+    Box<T>: GetHeapLinks,
+{
+    type Wrapped = Pin<Box<<Box<T> as GetHeapLinks>::EntryType>>;
+}
+
+#[cfg(feature = "alloc")]
+impl<T: ?Sized> GetHeapLinksWrapped for Pin<Arc<T>>
+where
+    Arc<T>: GetHeapLinks,
+{
+    type Wrapped = Pin<Arc<<Arc<T> as GetHeapLinks>::EntryType>>;
+}
+
+impl<'a, T: ?Sized> GetHeapLinksWrapped for Pin<&'a T>
+where
+    &'a T: GetHeapLinks,
+{
+    type Wrapped = Pin<&'a <&'a T as GetHeapLinks>::EntryType>;
+}
+
+/// The links used to link an object on a pairing heap.
+///
+/// Instances of this type are usually embedded in structures and returned in calls to
+/// [`GetHeapLinks::get_links`].
+pub struct HeapLinks<T: ?Sized> {
+    inserted: AtomicBool,
+    node: UnsafeCell<HeapNode<T>>,
+}
+
+// SAFETY: `HeapLinks` can be safely sent to other threads but we restrict it to being `Send` only
+// when the heap entries it points to are also `Send`.
+unsafe impl<T: ?Sized> Send for HeapLinks<T> {}
+
+// SAFETY: `HeapLinks` is usable from other threads via references but we restrict it to being
+// `Sync` only when the heap entries it points to are also `Sync`.
+unsafe impl<T: ?Sized> Sync for HeapLinks<T> {}
+
+impl<T: ?Sized> HeapLinks<T> {
+    loom_const_fn! {
+        /// Constructs a new [`HeapLinks`] instance that isn't inserted on any heaps yet.
+        pub fn new() -> Self {
+            Self {
+                inserted: AtomicBool::new(false),
+                node: UnsafeCell::new(HeapNode {
+                    child: None,
+                    next: None,
+                    prev: None,
+                }),
+            }
+        }
+    }
+
+    fn acquire_for_insertion(&self) -> bool {
+        self.inserted
+            .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_ok()
+    }
+
+    fn release_after_removal(&self) {
+        self.inserted.store(false, Ordering::Release);
+    }
+}
+
+impl<T: ?Sized> Default for HeapLinks<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The position of an entry in the tree of a heap.
+///
+/// Children are kept in a list of siblings: `child` is the first child, `next` the next sibling,
+/// and `prev` the previous sibling, or the parent for the first child. All of them are `None` for
+/// the root.
+struct HeapNode<T: ?Sized> {
+    child: Option<NonNull<T>>,
+    next: Option<NonNull<T>>,
+    prev: Option<NonNull<T>>,
+}
+
+/// A pairing heap of wrapped entries, with the smallest entry at the top.
+///
+/// Insertion and [`PairingHeap::decrease_key`] take constant time, and removals take amortised
+/// logarithmic time. Entries that compare equal are popped in an unspecified order.
+///
+/// # Invariants
+///
+/// `root` is either `None` or points to the smallest entry, and the entries form a heap-ordered
+/// tree through the nodes in their [`HeapLinks`]. The links of objects added to a heap are owned
+/// by the heap.
+pub struct PairingHeap<G: GetHeapLinksWrapped>
+where
+    G::EntryType: Ord,
+{
+    root: Option<NonNull<G::EntryType>>,
+    len: usize,
+}
+
+impl<G: GetHeapLinksWrapped> PairingHeap<G>
+where
+    G::EntryType: Ord,
+{
+    /// Constructs a new empty heap.
+    pub const fn new() -> Self {
+        Self { root: None, len: 0 }
+    }
+
+    /// Returns the number of entries in the heap.
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns whether the heap is empty.
+    pub const fn is_empty(&self) -> bool {
+        self.root.is_none()
+    }
+
+    /// Returns the node of `data`.
+    ///
+    /// # Safety
+    ///
+    /// `data` must be valid and its links must be owned by the caller.
+    unsafe fn node(data: NonNull<G::EntryType>) -> *mut HeapNode<G::EntryType> {
+        // SAFETY: `data` is valid by the safety requirements.
+        G::get_links(unsafe { data.as_ref() }).node.get()
+    }
+
+    /// Melds the trees rooted at `a` and `b`, returning the root of the result.
+    ///
+    /// # Safety
+    ///
+    /// `a` and `b` must be roots of disjoint trees owned by the heap, with no siblings.
+    unsafe fn meld(
+        a: Option<NonNull<G::EntryType>>,
+        b: Option<NonNull<G::EntryType>>,
+    ) -> Option<NonNull<G::EntryType>> {
+        let (a, b) = match (a, b) {
+            (Some(a), Some(b)) => (a, b),
+            (a, None) => return a,
+            (None, b) => return b,
+        };
+        // SAFETY: The entries are owned by the heap, so they are valid.
+        let (parent, child) = if unsafe { b.as_ref() < a.as_ref() } {
+            (b, a)
+        } else {
+            (a, b)
+        };
+        // SAFETY: The links of both entries are owned by the heap. `child` becomes the first child
+        // of `parent`.
+        unsafe {
+            let first = (*Self::node(parent)).child;
+            if let Some(first) = first {
+                (*Self::node(first)).prev = Some(child);
+            }
+            let child_node = Self::node(child);
+            (*child_node).next = first;
+            (*child_node).prev = Some(parent);
+            (*Self::node(parent)).child = Some(child);
+        }
+        Some(parent)
+    }
+
+    /// Melds a list of siblings into a single tree with the standard two-pass pairing.
+    ///
+    /// # Safety
+    ///
+    /// `first` must be the first of a list of siblings owned by the heap, whose parent has already
+    /// forgotten them.
+    unsafe fn merge_pairs(
+        mut first: Option<NonNull<G::EntryType>>,
+    ) -> Option<NonNull<G::EntryType>> {
+        // First pass: meld pairs from left to right, chaining the results in reverse order
+        // through their `next` links.
+        let mut pairs = None;
+        while let Some(a) = first {
+            // SAFETY: The siblings are owned by the heap. They are detached before being melded.
+            unsafe {
+                let b = (*Self::node(a)).next.take();
+                (*Self::node(a)).prev = None;
+                if let Some(b) = b {
+                    first = (*Self::node(b)).next.take();
+                    (*Self::node(b)).prev = None;
+                } else {
+                    first = None;
+                }
+                let pair = Self::meld(Some(a), b).unwrap();
+                (*Self::node(pair)).next = pairs;
+                pairs = Some(pair);
+            }
+        }
+
+        // Second pass: meld the pairs from right to left.
+        let mut root = None;
+        while let Some(pair) = pairs {
+            // SAFETY: The pairs are owned by the heap. They are detached before being melded.
+            unsafe {
+                pairs = (*Self::node(pair)).next.take();
+                root = Self::meld(root, Some(pair));
+            }
+        }
+        root
+    }
+
+    /// Detaches the subtree rooted at `data` from its parent, and returns the pointer to `data`
+    /// that was stored on the heap.
+    ///
+    /// # Safety
+    ///
+    /// `data` must be in this heap, and must not be its root.
+    unsafe fn cut(data: &G::EntryType) -> NonNull<G::EntryType> {
+        let node = G::get_links(data).node.get();
+        // SAFETY: The links of `data` and its neighbours are owned by the heap, and `data` has a
+        // parent or previous sibling as it isn't the root.
+        unsafe {
+            let prev = (*node).prev.take().unwrap();
+            let next = (*node).next.take();
+            let prev_node = Self::node(prev);
+            let is_first_child = (*prev_node)
+                .child
+                .is_some_and(|c| ptr::addr_eq(c.as_ptr(), data));
+            let slot = if is_first_child {
+                &mut (*prev_node).child
+            } else {
+                &mut (*prev_node).next
+            };
+            let stored = core::mem::replace(slot, next).unwrap();
+            if let Some(next) = next {
+                (*Self::node(next)).prev = Some(prev);
+            }
+            stored
+        }
+    }
+
+    /// Returns the position of `data` in the heap, or `None` if it isn't on any heap.
+    ///
+    /// # Safety
+    ///
+    /// `data` must be in this heap or in no heap.
+    unsafe fn position(&self, data: &G::EntryType) -> Option<Position> {
+        if self
+            .root
+            .is_some_and(|root| ptr::addr_eq(root.as_ptr(), data))
+        {
+            return Some(Position::Root);
+        }
+        // SAFETY: The links are owned by this heap if `data` is in it, and not modified by anyone
+        // otherwise.
+        let prev = unsafe { (*G::get_links(data).node.get()).prev };
+        // Entries in no heap have no parent or sibling.
+        prev.map(|_| Position::Child)
+    }
+
+    /// Adds the given object to the heap.
+    ///
+    /// It is dropped if it's already on this (or another) heap; this can happen for
+    /// reference-counted objects, so dropping means decrementing the reference count.
+    pub fn push(&mut self, data: G::Wrapped) {
+        let ptr = data.into_pointer();
+        // SAFETY: We took ownership of the entry, so it is valid.
+        if !G::get_links(unsafe { ptr.as_ref() }).acquire_for_insertion() {
+            // If insertion failed, rebuild object so that it can be freed.
+            // SAFETY: We just called `into_pointer` above.
+            unsafe { G::Wrapped::from_pointer(ptr) };
+            return;
+        }
+
+        // SAFETY: The links were just acquired, so the entry is a tree on its own, and the root
+        // is owned by the heap.
+        self.root = unsafe { Self::meld(self.root, Some(ptr)) };
+        self.len += 1;
+    }
+
+    /// Returns the smallest entry without removing it.
+    pub fn peek_min(&self) -> Option<&G::EntryType> {
+        // SAFETY: Objects must be kept alive while on the heap.
+        self.root.map(|root| unsafe { root.as_ref() })
+    }
+
+    /// Removes the smallest entry and returns it.
+    ///
+    /// Returns `None` if the heap is empty.
+    pub fn pop_min(&mut self) -> Option<G::Wrapped> {
+        let root = self.root?;
+        // SAFETY: The root is owned by the heap and its children are melded back into a single
+        // tree, which forgets it.
+        unsafe {
+            let children = (*Self::node(root)).child.take();
+            self.root = Self::merge_pairs(children);
+            G::get_links(root.as_ref()).release_after_removal();
+        }
+        self.len -= 1;
+        // SAFETY: Elements on the heap were inserted after a call to `into_pointer`.
+        Some(unsafe { G::Wrapped::from_pointer(root) })
+    }
+
+    /// Removes the given entry.
+    ///
+    /// # Safety
+    ///
+    /// Callers must ensure that `data` is either on this heap or in no heap. It being on another
+    /// heap leads to memory unsafety.
+    pub unsafe fn remove(&mut self, data: &G::Wrapped) -> Option<G::Wrapped> {
+        let entry = Wrapper::as_ref(data);
+        // SAFETY: The safety requirements guarantee that `data` is on this heap or in no heap.
+        match unsafe { self.position(entry) }? {
+            Position::Root => self.pop_min(),
+            Position::Child => {
+                // SAFETY: The entry is on this heap and isn't its root. Once detached, its
+                // children are melded back into the heap.
+                let ptr = unsafe {
+                    let ptr = Self::cut(entry);
+                    let children = (*Self::node(ptr)).child.take();
+                    let rest = Self::merge_pairs(children);
+                    self.root = Self::meld(self.root, rest);
+                    ptr
+                };
+                G::get_links(entry).release_after_removal();
+                self.len -= 1;
+                // SAFETY: Elements on the heap were inserted after a call to `into_pointer`.
+                Some(unsafe { G::Wrapped::from_pointer(ptr) })
+            }
+        }
+    }
+
+    /// Restores the heap order after the key of the given entry decreased.
+    ///
+    /// Entries are only accessible through shared references while they are on the heap, so keys
+    /// are changed through interior mutability, after which this must be called. Returns `false`
+    /// if the entry isn't on any heap.
+    ///
+    /// # Safety
+    ///
+    /// Callers must ensure that `data` is either on this heap or in no heap, and that its key
+    /// didn't increase: the heap would return entries out of order otherwise.
+    pub unsafe fn decrease_key(&mut self, data: &G::EntryType) -> bool {
+        // SAFETY: The safety requirements guarantee that `data` is on this heap or in no heap.
+        match unsafe { self.position(data) } {
+            None => false,
+            Some(Position::Root) => true,
+            Some(Position::Child) => {
+                // SAFETY: The entry is on this heap and isn't its root. Its subtree is still
+                // heap-ordered, so it can be melded back as is.
+                unsafe {
+                    let ptr = Self::cut(data);
+                    self.root = Self::meld(self.root, Some(ptr));
+                }
+                true
+            }
+        }
+    }
+}
+
+/// The position of an entry in a heap.
+enum Position {
+    /// The entry is the root.
+    Root,
+    /// The entry is the child of another entry.
+    Child,
+}
+
+impl<G: GetHeapLinksWrapped> Default for PairingHeap<G>
+where
+    G::EntryType: Ord,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<G: GetHeapLinksWrapped> Drop for PairingHeap<G>
+where
+    G::EntryType: Ord,
+{
+    fn drop(&mut self) {
+        while self.pop_min().is_some() {}
+    }
+}
+
+// SAFETY: The heap owns the wrapped entries, so it can be sent to other threads as long as they
+// can be sent too.
+unsafe impl<G: GetHeapLinksWrapped> Send for PairingHeap<G>
+where
+    G::EntryType: Ord,
+    G::Wrapped: Send,
+{
+}
+
+// SAFETY: Shared references to the heap only hand out shared references to its entries, which is
+// as much as shared references to the wrappers allow.
+unsafe impl<G: GetHeapLinksWrapped> Sync for PairingHeap<G>
+where
+    G::EntryType: Ord,
+    G::Wrapped: Sync,
+{
+}
+
+#[cfg(all(test, feature = "alloc", not(loom)))]
+mod tests {
+    use core::{
+        cmp::Ordering,
+        sync::atomic::{AtomicU64, Ordering::Relaxed},
+    };
+    use std::{sync::Arc, vec::Vec};
+
+    use super::{GetHeapLinks, HeapLinks, PairingHeap};
+
+    struct Timer {
+        deadline: AtomicU64,
+        links: HeapLinks<Self>,
+    }
+
+    impl Timer {
+        fn deadline(&self) -> u64 {
+            self.deadline.load(Relaxed)
+        }
+    }
+
+    impl GetHeapLinks for Timer {
+        type EntryType = Self;
+        fn get_links(obj: &Self) -> &HeapLinks<Self> {
+            &obj.links
+        }
+    }
+
+    impl PartialEq for Timer {
+        fn eq(&self, other: &Self) -> bool {
+            self.deadline() == other.deadline()
+        }
+    }
+
+    impl Eq for Timer {}
+
+    impl PartialOrd for Timer {
+        fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+            Some(self.cmp(other))
+        }
+    }
+
+    impl Ord for Timer {
+        fn cmp(&self, other: &Self) -> Ordering {
+            self.deadline().cmp(&other.deadline())
+        }
+    }
+
+    fn new_timer(deadline: u64) -> Arc<Timer> {
+        Arc::new(Timer {
+            deadline: AtomicU64::new(deadline),
+            links: HeapLinks::new(),
+        })
+    }
+
+    fn drain(heap: &mut PairingHeap<Arc<Timer>>) -> Vec<u64> {
+        core::iter::from_fn(|| heap.pop_min().map(|t| t.deadline())).collect()
+    }
+
+    #[test]
+    fn test_push_pop() {
+        let mut heap = PairingHeap::<Box<Timer>>::new();
+        assert!(heap.pop_min().is_none());
+        // A permutation of 0..100.
+        for d in (0..100u64).map(|i| i * 37 % 100) {
+            heap.push(Box::new(Timer {
+                deadline: AtomicU64::new(d),
+                links: HeapLinks::new(),
+            }));
+        }
+        assert_eq!(heap.len(), 100);
+        assert_eq!(heap.peek_min().unwrap().deadline(), 0);
+        let popped: Vec<_> = core::iter::from_fn(|| heap.pop_min().map(|t| t.deadline())).collect();
+        assert_eq!(popped, (0..100).collect::<Vec<_>>());
+        assert!(heap.is_empty());
+    }
+
+    #[test]
+    fn test_remove_and_decrease_key() {
+        let timers: Vec<_> = (0..50u64).map(|i| new_timer(100 + i * 13 % 50)).collect();
+        let mut heap = PairingHeap::<Arc<Timer>>::new();
+        for t in &timers {
+            heap.push(t.clone());
+        }
+        // Already on the heap, so this is dropped.
+        heap.push(timers[0].clone());
+        assert_eq!(heap.len(), 50);
+
+        // Pop once so that the entries form a deeper tree, then remove every third timer.
+        assert_eq!(heap.pop_min().unwrap().deadline(), 100);
+        let mut expected: Vec<_> = (101..150).collect();
+        for t in timers.iter().skip(1).step_by(3) {
+            let d = t.deadline();
+            // SAFETY: The timer is on this heap.
+            assert_eq!(unsafe { heap.remove(t) }.unwrap().deadline(), d);
+            // SAFETY: The timer is in no heap.
+            assert!(unsafe { heap.remove(t) }.is_none());
+            expected.retain(|&e| e != d);
+        }
+        assert_eq!(heap.len(), 32);
+
+        // Move some deadlines earlier, which only moves the timers that weren't removed.
+        for (i, t) in timers.iter().enumerate().skip(2).step_by(5) {
+            let d = t.deadline();
+            t.deadline.store(i as u64, Relaxed);
+            let on_heap = i % 3 != 1;
+            // SAFETY: The timer is either on this heap or in no heap, and its key decreased.
+            assert_eq!(unsafe { heap.decrease_key(t) }, on_heap);
+            if on_heap {
+                let pos = expected.iter().position(|&e| e == d).unwrap();
+                expected[pos] = i as u64;
+            }
+        }
+        expected.sort_unstable();
+        assert_eq!(expected[..8], [2, 12, 17, 27, 32, 42, 47, 104]);
+        assert_eq!(heap.len(), expected.len());
+        assert_eq!(drain(&mut heap), expected);
+        assert!(timers.iter().all(|t| Arc::strong_count(t) == 1));
+    }
+}
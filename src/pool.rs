@@ -0,0 +1,234 @@
+// SPDX-License-Identifier: GPL-2.0
+
+//! Boxes that return their memory to a pool.
+//!
+//! [`PoolBox`] is an owned pointer, like `Box`, to an object allocated from a [`Pool`] such as a
+//! slab cache, to which the memory is returned when the box is dropped. It can wrap list entries
+//! without the unstable `allocator_api`.
+
+use core::{
+    fmt,
+    marker::PhantomData,
+    mem::ManuallyDrop,
+    ops::{Deref, DerefMut},
+    ptr::{self, NonNull},
+};
+
+use crate::{
+    linked_list::{GetLinksWrapped, Wrapper},
+    GetLinks, Links,
+};
+
+/// A pool of memory for objects of type `T`.
+///
+/// The pool is identified by its type rather than by a value, so that a [`PoolBox`] can free its
+/// object from nothing but a pointer, e.g. when it comes back from a list.
+///
+/// # Safety
+///
+/// [`Pool::alloc`] must return memory that is valid for reads and writes of `T`, properly
+/// aligned, and not used by anything else until it is passed to [`Pool::dealloc`].
+pub unsafe trait Pool<T> {
+    /// Allocates memory for an object, or returns `None` if the pool is exhausted.
+    fn alloc() -> Option<NonNull<T>>;
+
+    /// Returns the memory of an object to the pool.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must have been returned by [`Pool::alloc`] and not freed since, and the object it
+    /// points to must have been dropped already.
+    unsafe fn dealloc(ptr: NonNull<T>);
+}
+
+/// An owned pointer to an object allocated from the pool `P`.
+///
+/// Dropping the box drops the object and returns its memory to the pool.
+pub struct PoolBox<T, P: Pool<T>> {
+    ptr: NonNull<T>,
+    _p: PhantomData<(T, P)>,
+}
+
+impl<T, P: Pool<T>> PoolBox<T, P> {
+    /// Moves `value` into memory allocated from the pool.
+    ///
+    /// Returns `value` back if the pool is exhausted.
+    pub fn try_new(value: T) -> Result<Self, T> {
+        let Some(ptr) = P::alloc() else {
+            return Err(value);
+        };
+        // SAFETY: The memory is valid for writes of `T` by the safety requirements of `Pool`.
+        unsafe { ptr.as_ptr().write(value) };
+        Ok(Self {
+            ptr,
+            _p: PhantomData,
+        })
+    }
+
+    /// Moves the object out of the box, returning its memory to the pool.
+    pub fn into_inner(this: Self) -> T {
+        let this = ManuallyDrop::new(this);
+        // SAFETY: The box owns a valid object, which isn't used again as the box is forgotten.
+        let value = unsafe { this.ptr.as_ptr().read() };
+        // SAFETY: The memory came from `P::alloc` and the object was moved out above.
+        unsafe { P::dealloc(this.ptr) };
+        value
+    }
+}
+
+impl<T, P: Pool<T>> Deref for PoolBox<T, P> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // SAFETY: The box owns a valid object.
+        unsafe { self.ptr.as_ref() }
+    }
+}
+
+impl<T, P: Pool<T>> DerefMut for PoolBox<T, P> {
+    fn deref_mut(&mut self) -> &mut T {
+        // SAFETY: The box owns a valid object, and we have exclusive access to the box.
+        unsafe { self.ptr.as_mut() }
+    }
+}
+
+impl<T, P: Pool<T>> Drop for PoolBox<T, P> {
+    fn drop(&mut self) {
+        // SAFETY: The box owns a valid object, which isn't used after it is dropped, and its
+        // memory came from `P::alloc`.
+        unsafe {
+            ptr::drop_in_place(self.ptr.as_ptr());
+            P::dealloc(self.ptr);
+        }
+    }
+}
+
+impl<T: fmt::Debug, P: Pool<T>> fmt::Debug for PoolBox<T, P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+// SAFETY: The box owns its object like `Box`, so it can be sent to other threads when the object
+// can. The pool is identified by its type and used through associated functions only.
+unsafe impl<T: Send, P: Pool<T>> Send for PoolBox<T, P> {}
+
+// SAFETY: The box only hands out shared references to its object from shared references to it.
+unsafe impl<T: Sync, P: Pool<T>> Sync for PoolBox<T, P> {}
+
+// SAFETY: The box owns its object, which stays in place until the box is recreated from the
+// pointer and is only mutated through it.
+unsafe impl<T, P: Pool<T>> Wrapper<T> for PoolBox<T, P> {
+    #[inline]
+    fn into_pointer(self) -> NonNull<T> {
+        ManuallyDrop::new(self).ptr
+    }
+
+    #[inline]
+    unsafe fn from_pointer(ptr: NonNull<T>) -> Self {
+        // SAFETY: The pointer comes from `into_pointer`, so it is owned and from `P::alloc`.
+        Self {
+            ptr,
+            _p: PhantomData,
+        }
+    }
+
+    #[inline]
+    fn as_ref(&self) -> &T {
+        self
+    }
+}
+
+impl<T, P> GetLinksWrapped for PoolBox<T, P>
+where
+    T: GetLinks<EntryType = T>,
+    P: Pool<T>,
+{
+    type Wrapped = PoolBox<T, P>;
+}
+
+impl<T: GetLinks, P: Pool<T>> GetLinks for PoolBox<T, P> {
+    type EntryType = T::EntryType;
+
+    #[inline]
+    fn get_links(data: &Self::EntryType) -> &Links<Self::EntryType> {
+        <T as GetLinks>::get_links(data)
+    }
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use core::{mem::MaybeUninit, ptr::NonNull};
+    use std::{
+        boxed::Box,
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+
+    use super::{Pool, PoolBox};
+    use crate::{GetLinks, Links, List};
+
+    struct Task {
+        id: usize,
+        links: Links<Self>,
+    }
+
+    impl GetLinks for Task {
+        type EntryType = Self;
+        fn get_links(obj: &Self) -> &Links<Self> {
+            &obj.links
+        }
+    }
+
+    /// A pool of at most `LIMIT` objects, which counts how many are in use.
+    struct CountingPool;
+
+    static IN_USE: AtomicUsize = AtomicUsize::new(0);
+    const LIMIT: usize = 3;
+
+    // SAFETY: The memory comes from a new box.
+    unsafe impl Pool<Task> for CountingPool {
+        fn alloc() -> Option<NonNull<Task>> {
+            IN_USE
+                .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| {
+                    (n < LIMIT).then_some(n + 1)
+                })
+                .ok()?;
+            let slot = Box::into_raw(Box::new(MaybeUninit::<Task>::uninit()));
+            NonNull::new(slot.cast())
+        }
+
+        unsafe fn dealloc(ptr: NonNull<Task>) {
+            // SAFETY: The pointer comes from a box of `MaybeUninit<Task>` in `alloc`.
+            drop(unsafe { Box::from_raw(ptr.as_ptr().cast::<MaybeUninit<Task>>()) });
+            IN_USE.fetch_sub(1, Ordering::Relaxed);
+        }
+    }
+
+    fn new_task(id: usize) -> Result<PoolBox<Task, CountingPool>, Task> {
+        PoolBox::try_new(Task {
+            id,
+            links: Links::new(),
+        })
+    }
+
+    #[test]
+    fn test_pool_box_list() {
+        let mut list = List::<PoolBox<Task, CountingPool>>::new();
+        for id in 0..LIMIT {
+            list.push_back(new_task(id).ok().unwrap());
+        }
+        // The pool is exhausted, so the task is handed back.
+        assert_eq!(new_task(LIMIT).err().unwrap().id, LIMIT);
+        assert_eq!(IN_USE.load(Ordering::Relaxed), LIMIT);
+
+        let mut front = list.pop_front().unwrap();
+        front.id = 10;
+        assert_eq!(PoolBox::into_inner(front).id, 10);
+        assert_eq!(IN_USE.load(Ordering::Relaxed), LIMIT - 1);
+
+        let ids: Vec<_> = list.iter().map(|t| t.id).collect();
+        assert_eq!(ids, [1, 2]);
+        drop(list);
+        assert_eq!(IN_USE.load(Ordering::Relaxed), 0);
+    }
+}
@@ -0,0 +1,232 @@
+// SPDX-License-Identifier: GPL-2.0
+
+//! Multi-level priority lists.
+//!
+//! An array of [`List`]s, one per priority level, plus a bitmap of the non-empty levels, as used
+//! by the run queues of schedulers.
+
+use core::slice;
+
+use crate::linked_list::{self, GetLinksWrapped, List};
+
+/// Returns the priority level of the entries of [`PriorityLists`].
+pub trait PriorityOf {
+    /// Returns the level of the entry, where 0 is the highest priority.
+    ///
+    /// It must not change while the entry is on the lists.
+    fn priority(&self) -> usize;
+}
+
+/// Lists of wrapped entries, one per priority level, where level 0 has the highest priority.
+///
+/// A bitmap of the non-empty levels makes [`PriorityLists::pop_highest`] take constant time.
+/// Entries of the same level are kept in FIFO order.
+///
+/// `LEVELS` can be at most 64.
+///
+/// # Invariants
+///
+/// Bit `n` of `bitmap` is set if and only if `lists[n]` isn't empty.
+pub struct PriorityLists<G: GetLinksWrapped, const LEVELS: usize> {
+    lists: [List<G>; LEVELS],
+    bitmap: u64,
+}
+
+impl<G: GetLinksWrapped, const LEVELS: usize> PriorityLists<G, LEVELS>
+where
+    G::EntryType: PriorityOf,
+{
+    const LEVELS_OK: () = assert!(LEVELS <= 64, "at most 64 priority levels are supported");
+
+    /// Constructs new empty lists.
+    pub const fn new() -> Self {
+        #[allow(clippy::let_unit_value)]
+        let () = Self::LEVELS_OK;
+        Self {
+            lists: [const { List::new() }; LEVELS],
+            bitmap: 0,
+        }
+    }
+
+    /// Returns whether all levels are empty.
+    pub const fn is_empty(&self) -> bool {
+        self.bitmap == 0
+    }
+
+    /// Returns the highest priority level that isn't empty.
+    pub const fn highest_level(&self) -> Option<usize> {
+        if self.bitmap == 0 {
+            None
+        } else {
+            Some(self.bitmap.trailing_zeros() as usize)
+        }
+    }
+
+    /// Adds the given object to the back of the list of the given level.
+    ///
+    /// It is dropped if it's already on this (or another) list; this can happen for
+    /// reference-counted objects, so dropping means decrementing the reference count.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `level` isn't less than `LEVELS`, or if it isn't the priority of the object, which
+    /// [`PriorityLists::remove`] relies on to find the list of the entry.
+    pub fn push(&mut self, level: usize, data: G::Wrapped) {
+        assert_eq!(
+            linked_list::Wrapper::as_ref(&data).priority(),
+            level,
+            "pushing an entry at a level other than its priority"
+        );
+        let list = &mut self.lists[level];
+        list.push_back(data);
+        if !list.is_empty() {
+            self.bitmap |= 1 << level;
+        }
+    }
+
+    /// Removes the first entry of the highest priority level that isn't empty and returns it.
+    ///
+    /// Returns `None` if all levels are empty.
+    pub fn pop_highest(&mut self) -> Option<G::Wrapped> {
+        let level = self.highest_level()?;
+        let data = self.lists[level].pop_front();
+        self.update_bitmap(level);
+        data
+    }
+
+    /// Removes the given entry from the list of its level.
+    ///
+    /// # Safety
+    ///
+    /// Callers must ensure that `data` is either on the list of its level or in no list. It being
+    /// on another list leads to memory unsafety.
+    pub unsafe fn remove(&mut self, data: &G::Wrapped) -> Option<G::Wrapped> {
+        let level = linked_list::Wrapper::as_ref(data).priority();
+        // SAFETY: The safety requirements guarantee that `data` is on this list or in no list.
+        let removed = unsafe { self.lists.get_mut(level)?.remove(data) };
+        self.update_bitmap(level);
+        removed
+    }
+
+    fn update_bitmap(&mut self, level: usize) {
+        if self.lists[level].is_empty() {
+            self.bitmap &= !(1 << level);
+        }
+    }
+
+    /// Returns an iterator over the entries in priority order.
+    pub fn iter(&self) -> Iter<'_, G> {
+        Iter {
+            lists: self.lists.iter(),
+            cur: None,
+        }
+    }
+}
+
+impl<G: GetLinksWrapped, const LEVELS: usize> Default for PriorityLists<G, LEVELS>
+where
+    G::EntryType: PriorityOf,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// An iterator over the entries of [`PriorityLists`] in priority order.
+pub struct Iter<'a, G: GetLinksWrapped> {
+    lists: slice::Iter<'a, List<G>>,
+    cur: Option<linked_list::Iterator<'a, G>>,
+}
+
+impl<'a, G: GetLinksWrapped> Iterator for Iter<'a, G> {
+    type Item = &'a G::EntryType;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(entry) = self.cur.as_mut().and_then(Iterator::next) {
+                return Some(entry);
+            }
+            self.cur = Some(self.lists.next()?.iter());
+        }
+    }
+}
+
+#[cfg(all(test, feature = "alloc", not(loom)))]
+mod tests {
+    use std::{sync::Arc, vec::Vec};
+
+    use super::{PriorityLists, PriorityOf};
+    use crate::{GetLinks, Links};
+
+    struct Task {
+        id: usize,
+        prio: usize,
+        links: Links<Self>,
+    }
+
+    impl GetLinks for Task {
+        type EntryType = Self;
+        fn get_links(obj: &Self) -> &Links<Self> {
+            &obj.links
+        }
+    }
+
+    impl PriorityOf for Task {
+        fn priority(&self) -> usize {
+            self.prio
+        }
+    }
+
+    fn new_task(id: usize, prio: usize) -> Arc<Task> {
+        Arc::new(Task {
+            id,
+            prio,
+            links: Links::new(),
+        })
+    }
+
+    #[test]
+    fn test_priority_order() {
+        let tasks: Vec<_> = (0..12).map(|id| new_task(id, id * 5 % 4)).collect();
+        let mut rq = PriorityLists::<Arc<Task>, 4>::new();
+        assert!(rq.is_empty());
+        for t in &tasks {
+            rq.push(t.prio, t.clone());
+        }
+        assert_eq!(rq.highest_level(), Some(0));
+
+        let ids: Vec<_> = rq.iter().map(|t| t.id).collect();
+        assert_eq!(ids, [0, 4, 8, 1, 5, 9, 2, 6, 10, 3, 7, 11]);
+
+        // Removing all tasks of a level clears it from the bitmap.
+        for id in [0, 4, 8] {
+            // SAFETY: The task is on the list of its level.
+            assert!(unsafe { rq.remove(&tasks[id]) }.is_some());
+        }
+        // SAFETY: The task is in no list.
+        assert!(unsafe { rq.remove(&tasks[0]) }.is_none());
+        assert_eq!(rq.highest_level(), Some(1));
+
+        let ids: Vec<_> = core::iter::from_fn(|| rq.pop_highest().map(|t| t.id)).collect();
+        assert_eq!(ids, [1, 5, 9, 2, 6, 10, 3, 7, 11]);
+        assert!(rq.is_empty());
+        assert!(tasks.iter().all(|t| Arc::strong_count(t) == 1));
+    }
+
+    #[test]
+    fn test_max_levels() {
+        let mut rq = PriorityLists::<Arc<Task>, 64>::new();
+        rq.push(63, new_task(0, 63));
+        rq.push(10, new_task(1, 10));
+        assert_eq!(rq.pop_highest().unwrap().id, 1);
+        assert_eq!(rq.pop_highest().unwrap().id, 0);
+        assert!(rq.pop_highest().is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "pushing an entry at a level other than its priority")]
+    fn test_push_at_wrong_level() {
+        let mut rq = PriorityLists::<Arc<Task>, 4>::new();
+        rq.push(1, new_task(0, 2));
+    }
+}
@@ -7,12 +7,17 @@
 //! TODO: This module is a work in progress.
 
 use core::{
-    cell::UnsafeCell,
-    iter, ptr,
+    cmp::Ordering as CmpOrdering,
+    fmt,
+    hash::{Hash, Hasher},
+    iter, mem,
+    pin::Pin,
+    ptr,
     ptr::NonNull,
-    sync::atomic::{AtomicBool, Ordering},
 };
 
+use crate::sync::{loom_const_fn, AtomicBool, Ordering, UnsafeCell};
+
 /// A descriptor of list elements.
 ///
 /// It describes the type of list elements and provides a function to determine how to get the
@@ -33,8 +38,8 @@ pub trait GetLinks {
 /// Instances of this type are usually embedded in structures and returned in calls to
 /// [`GetLinks::get_links`].
 pub struct Links<T: ?Sized> {
-    inserted: AtomicBool,
-    entry: UnsafeCell<ListEntry<T>>,
+    pub(crate) inserted: AtomicBool,
+    pub(crate) entry: UnsafeCell<ListEntry<T>>,
 }
 
 // SAFETY: `Links` can be safely sent to other threads but we restrict it to being `Send` only when
@@ -46,21 +51,23 @@ unsafe impl<T: ?Sized> Send for Links<T> {}
 unsafe impl<T: ?Sized> Sync for Links<T> {}
 
 impl<T: ?Sized> Links<T> {
-    /// Constructs a new [`Links`] instance that isn't inserted on any lists yet.
-    pub const fn new() -> Self {
-        Self {
-            inserted: AtomicBool::new(false),
-            entry: UnsafeCell::new(ListEntry::new()),
+    loom_const_fn! {
+        /// Constructs a new [`Links`] instance that isn't inserted on any lists yet.
+        pub fn new() -> Self {
+            Self {
+                inserted: AtomicBool::new(false),
+                entry: UnsafeCell::new(ListEntry::new()),
+            }
         }
     }
 
-    fn acquire_for_insertion(&self) -> bool {
+    pub(crate) fn acquire_for_insertion(&self) -> bool {
         self.inserted
             .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
             .is_ok()
     }
 
-    fn release_after_removal(&self) {
+    pub(crate) fn release_after_removal(&self) {
         self.inserted.store(false, Ordering::Release);
     }
 }
@@ -71,9 +78,57 @@ impl<T: ?Sized> Default for Links<T> {
     }
 }
 
-struct ListEntry<T: ?Sized> {
-    next: Option<NonNull<T>>,
-    prev: Option<NonNull<T>>,
+impl<T: ?Sized> Clone for Links<T> {
+    /// Returns new links that aren't inserted on any lists, as a clone of an entry is a different
+    /// object that isn't on the lists of the original.
+    fn clone(&self) -> Self {
+        Self::new()
+    }
+}
+
+/// Links don't take part in comparisons, so that comparing entries with derived implementations
+/// only compares their payloads.
+impl<T: ?Sized> PartialEq for Links<T> {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+impl<T: ?Sized> Eq for Links<T> {}
+
+impl<T: ?Sized> PartialOrd for Links<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<CmpOrdering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T: ?Sized> Ord for Links<T> {
+    fn cmp(&self, _other: &Self) -> CmpOrdering {
+        CmpOrdering::Equal
+    }
+}
+
+impl<T: ?Sized> Hash for Links<T> {
+    fn hash<H: Hasher>(&self, _state: &mut H) {}
+}
+
+impl<T: ?Sized> fmt::Debug for Links<T> {
+    /// Prints whether the links are on a list, without following the pointers to the neighbours.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = if self.inserted.load(Ordering::Relaxed) {
+            "linked"
+        } else {
+            "unlinked"
+        };
+        f.debug_tuple("Links")
+            .field(&format_args!("{state}"))
+            .finish()
+    }
+}
+
+pub(crate) struct ListEntry<T: ?Sized> {
+    pub(crate) next: Option<NonNull<T>>,
+    pub(crate) prev: Option<NonNull<T>>,
 }
 
 impl<T: ?Sized> ListEntry<T> {
@@ -83,8 +138,114 @@ impl<T: ?Sized> ListEntry<T> {
             prev: None,
         }
     }
+
+    /// Returns whether the entry is in no list.
+    pub(crate) fn is_unlinked(&self) -> bool {
+        self.next.is_none_or(is_poison)
+    }
+
+    /// Resets the links of `removed`, which was just unlinked, so that we know it's not on any
+    /// list.
+    ///
+    /// With the `poison` feature, the pointers are set to poison values rather than `None`, so
+    /// that following them from a stale pointer to the entry panics instead of silently walking
+    /// a list the entry is no longer on.
+    #[cfg_attr(not(feature = "poison"), allow(unused_variables))]
+    pub(crate) fn reset(&mut self, removed: NonNull<T>) {
+        #[cfg(feature = "poison")]
+        {
+            self.next = Some(removed.with_addr(LIST_POISON1));
+            self.prev = Some(removed.with_addr(LIST_POISON2));
+        }
+        #[cfg(not(feature = "poison"))]
+        {
+            self.next = None;
+            self.prev = None;
+        }
+    }
 }
 
+/// The address written to the `next` pointer of removed entries, as in Linux.
+#[cfg(feature = "poison")]
+const LIST_POISON1: core::num::NonZeroUsize = core::num::NonZeroUsize::new(0x100).unwrap();
+
+/// The address written to the `prev` pointer of removed entries, as in Linux.
+#[cfg(feature = "poison")]
+const LIST_POISON2: core::num::NonZeroUsize = core::num::NonZeroUsize::new(0x122).unwrap();
+
+/// Returns whether `ptr` was written to the links of a removed entry by [`ListEntry::reset`].
+#[inline]
+#[cfg_attr(not(feature = "poison"), allow(unused_variables))]
+fn is_poison<T: ?Sized>(ptr: NonNull<T>) -> bool {
+    #[cfg(feature = "poison")]
+    {
+        let addr = ptr.addr();
+        addr == LIST_POISON1 || addr == LIST_POISON2
+    }
+    #[cfg(not(feature = "poison"))]
+    false
+}
+
+/// Returns `ptr`, a link read from an entry that is expected to be on a list.
+///
+/// # Panics
+///
+/// Panics with `msg` if the `poison` feature is enabled and the entry was removed.
+#[inline]
+#[track_caller]
+fn check_poison<T: ?Sized>(ptr: Option<NonNull<T>>, msg: &str) -> Option<NonNull<T>> {
+    if ptr.is_some_and(is_poison) {
+        panic!("{msg}");
+    }
+    ptr
+}
+
+/// A corruption of the links of a [`RawList`], as detected by [`RawList::check_invariants`].
+///
+/// The index is the position of the offending entry, counting from the head of the list.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ListCorruption {
+    /// An entry on the list doesn't have its links marked as inserted.
+    NotInserted {
+        /// The position of the entry.
+        index: usize,
+    },
+    /// An entry on the list has no next or previous entry, or they were poisoned when it was
+    /// removed.
+    MissingLink {
+        /// The position of the entry.
+        index: usize,
+    },
+    /// The previous or next entry of an entry doesn't point back to it.
+    BrokenLink {
+        /// The position of the entry.
+        index: usize,
+    },
+    /// Following the next entries doesn't lead back to the head of the list.
+    Unterminated,
+}
+
+impl fmt::Display for ListCorruption {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotInserted { index } => {
+                write!(
+                    f,
+                    "entry {index} is on a list but its links aren't inserted"
+                )
+            }
+            Self::MissingLink { index } => write!(f, "entry {index} has no next or previous entry"),
+            Self::BrokenLink { index } => {
+                write!(f, "the neighbours of entry {index} don't point back to it")
+            }
+            Self::Unterminated => f.write_str("the list doesn't loop back to its head"),
+        }
+    }
+}
+
+impl core::error::Error for ListCorruption {}
+
 /// A linked list.
 ///
 /// # Invariants
@@ -120,15 +281,19 @@ impl<G: GetLinks> RawList<G> {
             // SAFETY: It's safe to get the previous entry of `existing` because the list cannot
             // change.
             let existing_links = unsafe { &mut *G::get_links(existing).entry.get() };
-            new_entry.next = existing_links.next;
+            new_entry.next = check_poison(
+                existing_links.next,
+                "inserting after an entry that was removed from its list",
+            );
             existing_links.next = new_ptr;
         }
 
-        new_entry.prev = Some(NonNull::from(existing));
-
         // SAFETY: It's safe to get the next entry of `existing` because the list cannot change.
         let next_links =
             unsafe { &mut *G::get_links(new_entry.next.unwrap().as_ref()).entry.get() };
+        // Reuse the pointer to `existing` stored on the list rather than deriving one from the
+        // reference, so that entries keep the provenance they were inserted with.
+        new_entry.prev = next_links.prev;
         next_links.prev = new_ptr;
     }
 
@@ -138,7 +303,25 @@ impl<G: GetLinks> RawList<G> {
     ///
     /// Callers must ensure that `existing` points to a valid entry that is on the list.
     pub unsafe fn insert_after(&mut self, existing: &G::EntryType, new: &G::EntryType) -> bool {
-        let links = G::get_links(new);
+        // SAFETY: The safety requirements are the same as `insert_after_internal`.
+        unsafe { self.insert_after_internal(existing, NonNull::from(new)) }
+    }
+
+    /// Inserts the object pointed to by `new` after `existing`.
+    ///
+    /// The list keeps `new` as is, so that the pointers it returns have the same provenance.
+    ///
+    /// # Safety
+    ///
+    /// Callers must ensure that `existing` points to a valid entry that is on the list, and the
+    /// validity of `new` while it is on the list.
+    pub(crate) unsafe fn insert_after_internal(
+        &mut self,
+        existing: &G::EntryType,
+        new: NonNull<G::EntryType>,
+    ) -> bool {
+        // SAFETY: `new` is valid by the safety requirements.
+        let links = G::get_links(unsafe { new.as_ref() });
         if !links.acquire_for_insertion() {
             // Nothing to do if already inserted.
             return false;
@@ -146,20 +329,45 @@ impl<G: GetLinks> RawList<G> {
 
         // SAFETY: The links are now owned by the list, so it is safe to get a mutable reference.
         let new_entry = unsafe { &mut *links.entry.get() };
-        self.insert_after_priv(existing, new_entry, Some(NonNull::from(new)));
+        self.insert_after_priv(existing, new_entry, Some(new));
+        self.debug_check();
         true
     }
 
-    fn push_back_internal(&mut self, new: &G::EntryType, front: bool) -> bool {
-        let links = G::get_links(new);
-        if !links.acquire_for_insertion() {
+    /// Adds the object pointed to by `new` to the back, or the front if `front` is `true`.
+    ///
+    /// The list keeps `new` as is, so that the pointers it returns have the same provenance.
+    ///
+    /// # Safety
+    ///
+    /// The caller must ensure the validity of `new` while it is on the list.
+    pub(crate) unsafe fn push_back_internal(
+        &mut self,
+        new: NonNull<G::EntryType>,
+        front: bool,
+    ) -> bool {
+        // SAFETY: `new` is valid by the safety requirements.
+        if !G::get_links(unsafe { new.as_ref() }).acquire_for_insertion() {
             // Nothing to do if already inserted.
             return false;
         }
 
-        // SAFETY: The links are now owned by the list, so it is safe to get a mutable reference.
-        let new_entry = unsafe { &mut *links.entry.get() };
-        let new_ptr = Some(NonNull::from(new));
+        // SAFETY: The links were just acquired for insertion.
+        unsafe { self.push_acquired(new, front) };
+        true
+    }
+
+    /// Adds an object whose links have already been acquired for insertion to the list.
+    ///
+    /// # Safety
+    ///
+    /// Callers must ensure that the links of `new` were acquired with
+    /// [`Links::acquire_for_insertion`] and are not on any list, and the validity of `new` while it
+    /// is on the list.
+    pub(crate) unsafe fn push_acquired(&mut self, new: NonNull<G::EntryType>, front: bool) {
+        // SAFETY: The links are owned by the list, so it is safe to get a mutable reference.
+        let new_entry = unsafe { &mut *G::get_links(new.as_ref()).entry.get() };
+        let new_ptr = Some(new);
         match self.back() {
             // SAFETY: `back` is valid as the list cannot change.
             Some(back) => {
@@ -175,38 +383,96 @@ impl<G: GetLinks> RawList<G> {
                 new_entry.prev = new_ptr;
             }
         }
-        true
+        self.debug_check();
     }
 
     /// Adds the given object to the end (back) of the list.
     ///
     /// Rawlist will save the reference as node ptr.
+    ///
+    /// # Safety
+    ///
     /// The caller must ensure the validity of the reference while it is on
-    /// the linked list.
+    /// the linked list. [`RawList::push_back_pinned`] ensures it for lists of pinned references.
     pub unsafe fn push_back(&mut self, new: &G::EntryType) -> bool {
-        self.push_back_internal(new, false)
+        // SAFETY: The safety requirements are the same as `push_back_internal`.
+        unsafe { self.push_back_internal(NonNull::from(new), false) }
     }
 
     /// Adds the given object to the first (front) of the list.
     ///
     /// Rawlist will save the reference as node ptr.
+    ///
+    /// # Safety
+    ///
     /// The caller must ensure the validity of the reference while it is on
-    /// the linked list.
+    /// the linked list. [`RawList::push_front_pinned`] ensures it for lists of pinned references.
     pub unsafe fn push_front(&mut self, new: &G::EntryType) -> bool {
-        self.push_back_internal(new, true)
+        // SAFETY: The safety requirements are the same as `push_back_internal`.
+        unsafe { self.push_back_internal(NonNull::from(new), true) }
     }
 
-    fn remove_internal(&mut self, data: &G::EntryType) -> bool {
+    /// Adds the object pointed to by `new` to the end (back) of the list.
+    ///
+    /// Unlike [`RawList::push_back`], the list stores `new` as is, so the pointers it later
+    /// returns, e.g. from [`RawList::pop_front`], keep its provenance. This is needed to access
+    /// the structure that contains an entry through them, with [`container_of!`](crate::container_of),
+    /// as pointers derived from a reference to the entry are only valid for the entry itself.
+    ///
+    /// # Safety
+    ///
+    /// The caller must ensure the validity of `new` while it is on the linked list.
+    pub unsafe fn push_back_ptr(&mut self, new: NonNull<G::EntryType>) -> bool {
+        // SAFETY: The safety requirements are the same as `push_back_internal`.
+        unsafe { self.push_back_internal(new, false) }
+    }
+
+    /// Removes the given entry, returning the pointer to it that was stored on the list.
+    ///
+    /// Returns `None` if the entry isn't on any list.
+    ///
+    /// # Safety
+    ///
+    /// Callers must ensure that `data` is either on this list or in no list.
+    pub(crate) unsafe fn remove_internal(
+        &mut self,
+        data: &G::EntryType,
+    ) -> Option<NonNull<G::EntryType>> {
+        // SAFETY: The safety requirements are the same as `unlink`.
+        let ptr = unsafe { self.unlink(data) }?;
+        G::get_links(data).release_after_removal();
+        Some(ptr)
+    }
+
+    /// Unlinks the given entry without releasing its links, so that it can be moved to another
+    /// list with [`RawList::push_acquired`] without another context acquiring them in between.
+    ///
+    /// Returns the pointer to the entry that was stored on the list, or `None` if the entry isn't
+    /// on any list.
+    ///
+    /// # Safety
+    ///
+    /// Callers must ensure that `data` is either on this list or in no list.
+    pub(crate) unsafe fn unlink(&mut self, data: &G::EntryType) -> Option<NonNull<G::EntryType>> {
         let links = G::get_links(data);
+        // SAFETY: The links are either owned by this list or unused.
+        if unsafe { (*links.entry.get()).is_unlinked() } {
+            // Nothing to do if the entry is not on the list.
+            return None;
+        }
+
+        // The pointer to `data` stored on the list is the `next` of the previous entry, which is
+        // `data` itself if it's the only one. Read it before taking a mutable reference to the
+        // links below, as they may be the same links.
+        // SAFETY: The links are owned by the list and the list cannot change.
+        let stored = unsafe {
+            let prev = (*links.entry.get()).prev?;
+            (*G::get_links(prev.as_ref()).entry.get()).next
+        };
 
         // SAFETY: The links are now owned by the list, so it is safe to get a mutable reference.
         let entry = unsafe { &mut *links.entry.get() };
-        let next = if let Some(next) = entry.next {
-            next
-        } else {
-            // Nothing to do if the entry is not on the list.
-            return false;
-        };
+        let next = entry.next?;
 
         if ptr::eq(data, next.as_ptr()) {
             // We're removing the only element.
@@ -228,10 +494,9 @@ impl<G: GetLinks> RawList<G> {
         }
 
         // Reset the links of the element we're removing so that we know it's not on any list.
-        entry.next = None;
-        entry.prev = None;
-        links.release_after_removal();
-        true
+        entry.reset(NonNull::from(data));
+        self.debug_check();
+        stored
     }
 
     /// Removes the given entry.
@@ -241,7 +506,8 @@ impl<G: GetLinks> RawList<G> {
     /// Callers must ensure that `data` is either on this list or in no list. It being on another
     /// list leads to memory unsafety.
     pub unsafe fn remove(&mut self, data: &G::EntryType) -> bool {
-        self.remove_internal(data)
+        // SAFETY: The safety requirements are the same as `remove_internal`.
+        unsafe { self.remove_internal(data) }.is_some()
     }
 
     fn pop_front_internal(&mut self) -> Option<NonNull<G::EntryType>> {
@@ -256,6 +522,13 @@ impl<G: GetLinks> RawList<G> {
         self.pop_front_internal()
     }
 
+    /// Get and Remove the last element of the list.
+    pub fn pop_back(&mut self) -> Option<NonNull<G::EntryType>> {
+        let back = self.back()?;
+        // SAFETY: The back is on the list as we just got it from there and it cannot change.
+        unsafe { self.remove_internal(back.as_ref()) }
+    }
+
     ///  Just Get and not remove the first element of the list.
     pub(crate) fn front(&self) -> Option<NonNull<G::EntryType>> {
         self.head
@@ -264,7 +537,8 @@ impl<G: GetLinks> RawList<G> {
     /// Just Get and not remove the last element of the list.
     pub(crate) fn back(&self) -> Option<NonNull<G::EntryType>> {
         // SAFETY: The links of head are owned by the list, so it is safe to get a reference.
-        unsafe { &*G::get_links(self.head?.as_ref()).entry.get() }.prev
+        let prev = unsafe { &*G::get_links(self.head?.as_ref()).entry.get() }.prev;
+        check_poison(prev, "the head of the list was removed from it")
     }
 
     /// Returns a cursor starting on the first element of the list.
@@ -281,6 +555,150 @@ impl<G: GetLinks> RawList<G> {
     pub fn cursor_front_mut(&mut self) -> CursorMut<'_, G> {
         CursorMut::new(self, self.front())
     }
+
+    /// Checks that the links of the entries on the list are consistent.
+    ///
+    /// This walks the list from the head, checking that the links of every entry are marked as
+    /// inserted, that its neighbours point back to it, and that the list loops back to the head.
+    /// Unlike the cursors, it stops after as many entries as could possibly fit in memory, so it
+    /// doesn't hang on a list whose head was unlinked.
+    ///
+    /// Corruption can only be detected while the entries it reaches are still alive, for example
+    /// after an entry was removed from or inserted on the wrong list; following pointers to freed
+    /// entries is still undefined behaviour.
+    pub fn check_invariants(&self) -> Result<(), ListCorruption> {
+        let Some(head) = self.head else {
+            return Ok(());
+        };
+        // Each entry has its own links, so there can't be more entries than this.
+        let max_len = isize::MAX as usize / mem::size_of::<Links<G::EntryType>>();
+        let mut cur = head;
+        for index in 0..max_len {
+            // SAFETY: Objects must be kept alive while on the list.
+            let links = G::get_links(unsafe { cur.as_ref() });
+            if !links.inserted.load(Ordering::Relaxed) {
+                return Err(ListCorruption::NotInserted { index });
+            }
+            // SAFETY: We have a shared ref to the linked list, so the links can't change.
+            let entry = unsafe { &*links.entry.get() };
+            let (Some(next), Some(prev)) = (entry.next, entry.prev) else {
+                return Err(ListCorruption::MissingLink { index });
+            };
+            if is_poison(next) || is_poison(prev) {
+                return Err(ListCorruption::MissingLink { index });
+            }
+            // SAFETY: The neighbours are on the list, so they are alive and their links can't
+            // change.
+            let (prev_next, next_prev) = unsafe {
+                (
+                    (*G::get_links(prev.as_ref()).entry.get()).next,
+                    (*G::get_links(next.as_ref()).entry.get()).prev,
+                )
+            };
+            let points_back = |p: Option<NonNull<G::EntryType>>| {
+                p.is_some_and(|p| ptr::addr_eq(p.as_ptr(), cur.as_ptr()))
+            };
+            if !points_back(prev_next) || !points_back(next_prev) {
+                return Err(ListCorruption::BrokenLink { index });
+            }
+            if ptr::addr_eq(next.as_ptr(), head.as_ptr()) {
+                return Ok(());
+            }
+            cur = next;
+        }
+        Err(ListCorruption::Unterminated)
+    }
+
+    /// Panics if the list is corrupted, when the `debug-checks` feature is enabled.
+    #[inline]
+    fn debug_check(&self) {
+        #[cfg(feature = "debug-checks")]
+        if let Err(err) = self.check_invariants() {
+            panic!("list corruption: {err}");
+        }
+    }
+}
+
+/// Lists of pinned references can have entries added without `unsafe`: the type of the list
+/// borrows the entries for `'a`, so they can neither move nor be freed while it is in use.
+impl<'a, T: GetLinks<EntryType = T> + ?Sized> RawList<Pin<&'a T>> {
+    /// Adds the given pinned object to the end (back) of the list.
+    ///
+    /// Returns `false` if the object is already on a list.
+    pub fn push_back_pinned(&mut self, new: Pin<&'a T>) -> bool {
+        // SAFETY: The object is borrowed for as long as the list can be used.
+        unsafe { self.push_back_internal(NonNull::from(Pin::get_ref(new)), false) }
+    }
+
+    /// Adds the given pinned object to the first (front) of the list.
+    ///
+    /// Returns `false` if the object is already on a list.
+    pub fn push_front_pinned(&mut self, new: Pin<&'a T>) -> bool {
+        // SAFETY: The object is borrowed for as long as the list can be used.
+        unsafe { self.push_back_internal(NonNull::from(Pin::get_ref(new)), true) }
+    }
+}
+
+impl<G: GetLinks> Default for RawList<G> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<G: GetLinks> fmt::Debug for RawList<G>
+where
+    G::EntryType: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+/// Lists are compared entry by entry, as sequences.
+impl<G: GetLinks> PartialEq for RawList<G>
+where
+    G::EntryType: PartialEq,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.iter().eq(other.iter())
+    }
+}
+
+impl<G: GetLinks> Eq for RawList<G> where G::EntryType: Eq {}
+
+/// Lists are ordered lexicographically by their entries.
+impl<G: GetLinks> PartialOrd for RawList<G>
+where
+    G::EntryType: PartialOrd,
+{
+    fn partial_cmp(&self, other: &Self) -> Option<CmpOrdering> {
+        self.iter().partial_cmp(other.iter())
+    }
+}
+
+impl<G: GetLinks> Ord for RawList<G>
+where
+    G::EntryType: Ord,
+{
+    fn cmp(&self, other: &Self) -> CmpOrdering {
+        self.iter().cmp(other.iter())
+    }
+}
+
+impl<G: GetLinks> Hash for RawList<G>
+where
+    G::EntryType: Hash,
+{
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        // The length isn't known upfront, so it is hashed after the entries to keep lists that are
+        // prefixes of one another apart.
+        let mut len = 0;
+        for entry in self {
+            entry.hash(state);
+            len += 1;
+        }
+        state.write_usize(len);
+    }
 }
 
 struct CommonCursor<G: GetLinks> {
@@ -299,8 +717,12 @@ impl<G: GetLinks> CommonCursor<G> {
                 if let Some(head) = list.head {
                     // SAFETY: We have a shared ref to the linked list, so the links can't change.
                     let links = unsafe { &*G::get_links(cur.as_ref()).entry.get() };
-                    if !ptr::addr_eq(links.next.unwrap().as_ptr(), head.as_ptr()) {
-                        self.cur = links.next;
+                    let next = check_poison(
+                        links.next,
+                        "list cursor moved from an entry that was removed from the list",
+                    );
+                    if !ptr::addr_eq(next.unwrap().as_ptr(), head.as_ptr()) {
+                        self.cur = next;
                     }
                 }
             }
@@ -322,7 +744,10 @@ impl<G: GetLinks> CommonCursor<G> {
                 };
                 // SAFETY: There's a shared ref to the list, so the links can't change.
                 let links = unsafe { &*G::get_links(next.as_ref()).entry.get() };
-                self.cur = links.prev;
+                self.cur = check_poison(
+                    links.prev,
+                    "list cursor moved from an entry that was removed from the list",
+                );
             }
         }
     }
@@ -382,10 +807,14 @@ impl<'a, G: GetLinks> CursorMut<'a, G> {
         }
     }
 
-    pub fn current(&mut self) -> Option<&mut G::EntryType> {
+    /// Returns the element the cursor is currently positioned on.
+    ///
+    /// Only shared references are handed out, as other entries on the list hold pointers to the
+    /// same objects; use interior mutability to modify them.
+    pub fn current(&self) -> Option<&G::EntryType> {
         let cur = self.cursor.cur?;
         // SAFETY: Objects must be kept alive while on the list.
-        Some(unsafe { &mut *cur.as_ptr() })
+        Some(unsafe { cur.as_ref() })
     }
 
     /// Removes the entry the cursor is pointing to and advances the cursor to the next entry. It
@@ -398,18 +827,20 @@ impl<'a, G: GetLinks> CursorMut<'a, G> {
         Some(entry)
     }
 
-    pub fn peek_next(&mut self) -> Option<&mut G::EntryType> {
+    /// Returns the element immediately after the one the cursor is positioned on.
+    pub fn peek_next(&self) -> Option<&G::EntryType> {
         let mut new = CommonCursor::new(self.cursor.cur);
         new.move_next(self.list);
         // SAFETY: Objects must be kept alive while on the list.
-        Some(unsafe { &mut *new.cur?.as_ptr() })
+        Some(unsafe { new.cur?.as_ref() })
     }
 
-    pub fn peek_prev(&mut self) -> Option<&mut G::EntryType> {
+    /// Returns the element immediately before the one the cursor is positioned on.
+    pub fn peek_prev(&self) -> Option<&G::EntryType> {
         let mut new = CommonCursor::new(self.cursor.cur);
         new.move_prev(self.list);
         // SAFETY: Objects must be kept alive while on the list.
-        Some(unsafe { &mut *new.cur?.as_ptr() })
+        Some(unsafe { new.cur?.as_ref() })
     }
 
     pub fn move_next(&mut self) {
@@ -420,6 +851,26 @@ impl<'a, G: GetLinks> CursorMut<'a, G> {
     pub fn move_prev(&mut self) {
         self.cursor.move_prev(self.list);
     }
+
+    /// Returns the position of the cursor, counting from the front of the list.
+    ///
+    /// This walks the list, so it takes linear time.
+    pub(crate) fn index(&self) -> Option<usize> {
+        let cur = self.cursor.cur?;
+        self.list.iter().position(|e| ptr::addr_eq(e, cur.as_ptr()))
+    }
+}
+
+impl<G: GetLinks> fmt::Debug for CursorMut<'_, G>
+where
+    G::EntryType: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CursorMut")
+            .field("index", &self.index())
+            .field("current", &self.current())
+            .finish()
+    }
 }
 
 impl<'a, G: GetLinks> iter::IntoIterator for &'a RawList<G> {
@@ -463,7 +914,42 @@ impl<G: GetLinks> iter::DoubleEndedIterator for Iterator<'_, G> {
     }
 }
 
-#[cfg(test)]
+impl<G: GetLinks> fmt::Debug for Iterator<'_, G>
+where
+    G::EntryType: fmt::Debug,
+{
+    /// Prints the entries that are left, starting from the front.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let remaining = Remaining {
+            list: self.cursor_front.list,
+            cur: self.cursor_front.cursor.cur,
+        };
+        f.debug_tuple("Iterator").field(&remaining).finish()
+    }
+}
+
+/// The entries of a list from `cur` to the back, printed as a list.
+struct Remaining<'a, G: GetLinks> {
+    list: &'a RawList<G>,
+    cur: Option<NonNull<G::EntryType>>,
+}
+
+impl<G: GetLinks> fmt::Debug for Remaining<'_, G>
+where
+    G::EntryType: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut cursor = Cursor::new(self.list, self.cur);
+        let entries = iter::from_fn(|| {
+            let ret = cursor.current()?;
+            cursor.move_next();
+            Some(ret)
+        });
+        f.debug_list().entries(entries).finish()
+    }
+}
+
+#[cfg(all(test, not(loom)))]
 mod tests {
     extern crate alloc;
     use alloc::{boxed::Box, vec::Vec};
@@ -480,9 +966,9 @@ mod tests {
         }
     }
 
+    #[allow(clippy::vec_box)]
     fn build_vector(size: usize) -> Vec<Box<Example>> {
-        let mut v = Vec::new();
-        v.reserve(size);
+        let mut v = Vec::with_capacity(size);
         for _ in 0..size {
             v.push(Box::new(Example {
                 links: super::Links::new(),
@@ -494,6 +980,7 @@ mod tests {
     #[track_caller]
     fn assert_list_contents(v: &[Box<Example>], list: &super::RawList<Example>) {
         let n = v.len();
+        assert_eq!(list.check_invariants(), Ok(()));
 
         // Assert that the list is ok going forward.
         let mut count = 0;
@@ -527,10 +1014,10 @@ mod tests {
                 let mut list = super::RawList::<Example>::new();
 
                 // Build list.
-                for j in 0..n {
+                for e in v.iter() {
                     // SAFETY: The entry was allocated above, it's not in any lists yet, is never
                     // moved, and outlives the list.
-                    unsafe { list.push_back(&v[j]) };
+                    unsafe { list.push_back(e) };
                 }
 
                 // Call the test case.
@@ -571,6 +1058,29 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_push_pinned() {
+        let a = core::pin::pin!(Example {
+            links: super::Links::new(),
+        });
+        let b = core::pin::pin!(Example {
+            links: super::Links::new(),
+        });
+        let (a, b) = (a.into_ref(), b.into_ref());
+        let mut list = super::RawList::<core::pin::Pin<&Example>>::new();
+        assert!(list.push_back_pinned(b));
+        assert!(list.push_front_pinned(a));
+        // Already on the list.
+        assert!(!list.push_back_pinned(a));
+        assert_eq!(list.check_invariants(), Ok(()));
+
+        let entries: Vec<_> = list.iter().map(|e| e as *const Example).collect();
+        assert_eq!(entries, [&*a as *const Example, &*b]);
+        assert!(core::ptr::eq(list.pop_front().unwrap().as_ptr(), &*a));
+        assert!(core::ptr::eq(list.pop_back().unwrap().as_ptr(), &*b));
+        assert!(list.is_empty());
+    }
+
     #[test]
     fn test_one_removal() {
         test_each_element(1, 10, |v, list, i, _| {
@@ -588,8 +1098,205 @@ mod tests {
             // SAFETY: The i-th element was added to the list above, and wasn't removed yet.
             // Additionally, the new element isn't in any list yet, isn't moved, and outlives
             // the list.
-            unsafe { list.insert_after(&*v[i], &*extra) };
             v.insert(i + 1, extra);
+            unsafe { list.insert_after(&*v[i], &*v[i + 1]) };
+        });
+    }
+
+    #[test]
+    fn test_check_invariants() {
+        use super::{ListCorruption, Ordering};
+
+        let v = build_vector(3);
+        let mut list = super::RawList::<Example>::new();
+        let mut other = super::RawList::<Example>::new();
+        for e in &v[..2] {
+            // SAFETY: The entry was allocated above, it's not in any lists yet, is never moved,
+            // and outlives the list.
+            unsafe { list.push_back(e) };
+        }
+        // SAFETY: Same as above.
+        unsafe { other.push_back(&v[2]) };
+
+        v[1].links.inserted.store(false, Ordering::Relaxed);
+        assert_eq!(
+            list.check_invariants(),
+            Err(ListCorruption::NotInserted { index: 1 })
+        );
+        v[1].links.inserted.store(true, Ordering::Relaxed);
+
+        // Violate the safety requirements of `remove` by removing the head from another list,
+        // which leaves `list` pointing to an entry that is no longer linked.
+        // SAFETY: Not really safe, but all entries outlive both lists and no cursor walks
+        // `list` afterwards.
+        unsafe { other.remove(&v[0]) };
+        assert_eq!(other.check_invariants(), Ok(()));
+        assert_eq!(
+            list.check_invariants(),
+            Err(ListCorruption::NotInserted { index: 0 })
+        );
+        assert_eq!(
+            ListCorruption::NotInserted { index: 0 }.to_string(),
+            "entry 0 is on a list but its links aren't inserted"
+        );
+    }
+
+    #[cfg(feature = "poison")]
+    #[test]
+    fn test_poison() {
+        let v = build_vector(2);
+        let mut list = super::RawList::<Example>::new();
+        for e in &v {
+            // SAFETY: The entry was allocated above, it's not in any lists yet, is never moved,
+            // and outlives the list.
+            unsafe { list.push_back(e) };
+        }
+
+        // SAFETY: The entry is on this list.
+        assert!(unsafe { list.remove(&v[0]) });
+        // SAFETY: The links of removed entries are unused.
+        let entry = unsafe { &*v[0].links.entry.get() };
+        assert_eq!(entry.next.unwrap().addr(), super::LIST_POISON1);
+        assert_eq!(entry.prev.unwrap().addr(), super::LIST_POISON2);
+        assert!(entry.is_unlinked());
+
+        // Removed entries can still be removed again (as a no-op) and reinserted.
+        // SAFETY: The entry is in no list.
+        assert!(!unsafe { list.remove(&v[0]) });
+        // SAFETY: Same as above.
+        assert!(unsafe { list.push_back(&v[0]) });
+        assert_eq!(list.check_invariants(), Ok(()));
+    }
+
+    #[cfg(feature = "poison")]
+    #[test]
+    #[should_panic(expected = "inserting after an entry that was removed from its list")]
+    fn test_poison_insert_after_removed() {
+        let v = build_vector(3);
+        let mut list = super::RawList::<Example>::new();
+        for e in &v[..2] {
+            // SAFETY: The entry was allocated above, it's not in any lists yet, is never moved,
+            // and outlives the list.
+            unsafe { list.push_back(e) };
+        }
+        // SAFETY: The entry is on this list.
+        unsafe { list.remove(&v[1]) };
+        // Violates the safety requirements of `insert_after`, which is caught before following
+        // the links of the removed entry.
+        // SAFETY: Not really safe, see above.
+        unsafe { list.insert_after(&v[1], &v[2]) };
+    }
+
+    #[cfg(feature = "debug-checks")]
+    #[test]
+    #[should_panic(expected = "list corruption")]
+    fn test_debug_checks() {
+        let v = build_vector(2);
+        let mut list = super::RawList::<Example>::new();
+        // SAFETY: The entry was allocated above, it's not in any lists yet, is never moved, and
+        // outlives the list.
+        unsafe { list.push_back(&v[0]) };
+        v[0].links.inserted.store(false, super::Ordering::Relaxed);
+        // SAFETY: Same as above.
+        unsafe { list.push_back(&v[1]) };
+    }
+}
+
+#[cfg(all(test, loom))]
+mod loom_tests {
+    extern crate alloc;
+    use alloc::sync::Arc;
+
+    use crate::{
+        sync::{AtomicBool, Ordering},
+        GetLinks, Links, List,
+    };
+
+    struct Example {
+        links: Links<Self>,
+    }
+
+    impl GetLinks for Example {
+        type EntryType = Self;
+        fn get_links(obj: &Self) -> &Links<Self> {
+            &obj.links
+        }
+    }
+
+    fn new_example() -> Arc<Example> {
+        Arc::new(Example {
+            links: Links::new(),
+        })
+    }
+
+    #[test]
+    fn loom_racing_insertions() {
+        loom::model(|| {
+            let e = new_example();
+            let handles: [_; 2] = core::array::from_fn(|_| {
+                let e = e.clone();
+                loom::thread::spawn(move || {
+                    let mut list = List::<Arc<Example>>::new();
+                    list.push_back(e);
+                    list
+                })
+            });
+            let lists = handles.map(|h| h.join().unwrap());
+
+            // Exactly one of the lists won the links; the other reference was dropped.
+            assert_eq!(lists.iter().filter(|l| !l.is_empty()).count(), 1);
+            assert_eq!(Arc::strong_count(&e), 2);
+            drop(lists);
+            assert_eq!(Arc::strong_count(&e), 1);
+        });
+    }
+
+    #[test]
+    fn loom_remove_racing_with_reinsert() {
+        loom::model(|| {
+            let e = new_example();
+            let removed = Arc::new(AtomicBool::new(false));
+            let mut first = List::<Arc<Example>>::new();
+            first.push_back(e.clone());
+
+            let remover = {
+                let e = e.clone();
+                let removed = removed.clone();
+                loom::thread::spawn(move || {
+                    // SAFETY: `e` stays on `first` until it is removed here.
+                    let entry = unsafe { first.remove(&e) };
+                    assert!(entry.is_some());
+                    assert!(first.is_empty());
+                    drop(entry);
+                    removed.store(true, Ordering::Release);
+                })
+            };
+            let inserter = {
+                let e = e.clone();
+                loom::thread::spawn(move || {
+                    let after_removal = removed.load(Ordering::Acquire);
+                    let mut second = List::<Arc<Example>>::new();
+                    second.push_back(e);
+                    (after_removal, second)
+                })
+            };
+
+            remover.join().unwrap();
+            let (after_removal, mut second) = inserter.join().unwrap();
+
+            // The insertion succeeds if and only if it observed the links being released by the
+            // removal, which it must have if it started after the removal completed. Otherwise,
+            // the reference it was given was dropped.
+            let inserted = !second.is_empty();
+            assert!(inserted || !after_removal);
+            assert_eq!(Arc::strong_count(&e), 1 + usize::from(inserted));
+            if inserted {
+                let entry = second.pop_front().unwrap();
+                assert!(Arc::ptr_eq(&entry, &e));
+                assert!(second.is_empty());
+                drop(entry);
+            }
+            assert_eq!(Arc::strong_count(&e), 1);
         });
     }
 }
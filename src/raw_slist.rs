@@ -0,0 +1,474 @@
+// SPDX-License-Identifier: GPL-2.0
+
+//! Raw singly linked lists.
+//!
+//! Singly linked counterpart of [`crate::RawList`], for lists that are only ever manipulated at
+//! the front (e.g., free lists and deferred work queues).
+
+use core::{
+    iter,
+    ptr::{self, NonNull},
+};
+
+use crate::sync::{loom_const_fn, AtomicBool, Ordering, UnsafeCell};
+
+/// A descriptor of singly linked list elements.
+///
+/// It describes the type of list elements and provides a function to determine how to get the
+/// links to be used on a list.
+///
+/// A type that may be in multiple lists simultaneously needs to implement one of these for each
+/// simultaneous list.
+pub trait GetSLinks {
+    /// The type of the entries in the list.
+    type EntryType: ?Sized;
+
+    /// Returns the links to be used when linking an entry within a list.
+    fn get_links(data: &Self::EntryType) -> &SLinks<Self::EntryType>;
+}
+
+/// The links used to link an object on a singly linked list.
+///
+/// Instances of this type are usually embedded in structures and returned in calls to
+/// [`GetSLinks::get_links`].
+pub struct SLinks<T: ?Sized> {
+    inserted: AtomicBool,
+    next: UnsafeCell<Option<NonNull<T>>>,
+}
+
+// SAFETY: `SLinks` can be safely sent to other threads but we restrict it to being `Send` only
+// when the list entries it points to are also `Send`.
+unsafe impl<T: ?Sized> Send for SLinks<T> {}
+
+// SAFETY: `SLinks` is usable from other threads via references but we restrict it to being `Sync`
+// only when the list entries it points to are also `Sync`.
+unsafe impl<T: ?Sized> Sync for SLinks<T> {}
+
+impl<T: ?Sized> SLinks<T> {
+    loom_const_fn! {
+        /// Constructs a new [`SLinks`] instance that isn't inserted on any lists yet.
+        pub fn new() -> Self {
+            Self {
+                inserted: AtomicBool::new(false),
+                next: UnsafeCell::new(None),
+            }
+        }
+    }
+
+    fn acquire_for_insertion(&self) -> bool {
+        self.inserted
+            .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_ok()
+    }
+
+    fn release_after_removal(&self) {
+        self.inserted.store(false, Ordering::Release);
+    }
+}
+
+impl<T: ?Sized> Default for SLinks<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A singly linked list.
+///
+/// # Invariants
+///
+/// The links of objects added to a list are owned by the list.
+pub struct RawSList<G: GetSLinks> {
+    head: Option<NonNull<G::EntryType>>,
+}
+
+impl<G: GetSLinks> RawSList<G> {
+    /// Constructs a new empty RawSList.
+    pub const fn new() -> Self {
+        Self { head: None }
+    }
+
+    /// Returns an iterator for the list starting at the first entry.
+    pub fn iter(&self) -> Iterator<'_, G> {
+        Iterator::new(self.head)
+    }
+
+    /// Returns whether the RawSList is empty.
+    pub const fn is_empty(&self) -> bool {
+        self.head.is_none()
+    }
+
+    /// Returns the `next` field of the links of `data`.
+    ///
+    /// # Safety
+    ///
+    /// `data` must be valid and its links must be owned by the caller.
+    unsafe fn next_of(data: NonNull<G::EntryType>) -> *mut Option<NonNull<G::EntryType>> {
+        // SAFETY: `data` is valid by the safety requirements.
+        G::get_links(unsafe { data.as_ref() }).next.get()
+    }
+
+    /// Adds the given object to the first (front) of the list.
+    ///
+    /// Returns `false` if the object is already on a list.
+    ///
+    /// # Safety
+    ///
+    /// The caller must ensure the validity of the reference while it is on the list.
+    pub unsafe fn push_front(&mut self, new: &G::EntryType) -> bool {
+        // SAFETY: The safety requirements are the same as `push_front_internal`.
+        unsafe { self.push_front_internal(NonNull::from(new)) }
+    }
+
+    /// Adds the object pointed to by `new` to the first (front) of the list.
+    ///
+    /// The list keeps `new` as is, so that the pointers it returns have the same provenance.
+    ///
+    /// # Safety
+    ///
+    /// The caller must ensure the validity of `new` while it is on the list.
+    pub(crate) unsafe fn push_front_internal(&mut self, new: NonNull<G::EntryType>) -> bool {
+        // SAFETY: `new` is valid by the safety requirements.
+        if !G::get_links(unsafe { new.as_ref() }).acquire_for_insertion() {
+            // Nothing to do if already inserted.
+            return false;
+        }
+
+        // SAFETY: The links are now owned by the list.
+        unsafe { *Self::next_of(new) = self.head };
+        self.head = Some(new);
+        true
+    }
+
+    /// Inserts the given object after `existing`.
+    ///
+    /// Returns `false` if the object is already on a list.
+    ///
+    /// # Safety
+    ///
+    /// Callers must ensure that `existing` points to a valid entry that is on the list, and the
+    /// validity of `new` while it is on the list.
+    pub unsafe fn insert_after(&mut self, existing: &G::EntryType, new: &G::EntryType) -> bool {
+        // SAFETY: The safety requirements are the same as `insert_after_internal`.
+        unsafe { self.insert_after_internal(existing, NonNull::from(new)) }
+    }
+
+    /// Inserts the object pointed to by `new` after `existing`.
+    ///
+    /// The list keeps `new` as is, so that the pointers it returns have the same provenance.
+    ///
+    /// # Safety
+    ///
+    /// Callers must ensure that `existing` points to a valid entry that is on the list, and the
+    /// validity of `new` while it is on the list.
+    pub(crate) unsafe fn insert_after_internal(
+        &mut self,
+        existing: &G::EntryType,
+        new: NonNull<G::EntryType>,
+    ) -> bool {
+        // SAFETY: `new` is valid by the safety requirements.
+        if !G::get_links(unsafe { new.as_ref() }).acquire_for_insertion() {
+            // Nothing to do if already inserted.
+            return false;
+        }
+
+        // SAFETY: Both entries are owned by the list and cannot change. Only the links of
+        // `existing` are written through the pointer derived from the reference.
+        unsafe {
+            let existing_next = Self::next_of(NonNull::from(existing));
+            *Self::next_of(new) = *existing_next;
+            *existing_next = Some(new);
+        }
+        true
+    }
+
+    /// Unlinks `cur`, whose predecessor is `prev` (or the head if `None`).
+    ///
+    /// # Safety
+    ///
+    /// `cur` must be on the list, and `prev` must be its predecessor.
+    unsafe fn unlink(&mut self, prev: Option<NonNull<G::EntryType>>, cur: NonNull<G::EntryType>) {
+        // SAFETY: `cur` and `prev` are on the list, so their links are owned by it.
+        unsafe {
+            let next = (*Self::next_of(cur)).take();
+            match prev {
+                Some(prev) => *Self::next_of(prev) = next,
+                None => self.head = next,
+            }
+            G::get_links(cur.as_ref()).release_after_removal();
+        }
+    }
+
+    /// Removes the given entry.
+    ///
+    /// This walks the list to find the predecessor of `data`, so it takes linear time.
+    ///
+    /// # Safety
+    ///
+    /// Callers must ensure that `data` is either on this list or in no list. It being on another
+    /// list leads to memory unsafety.
+    pub unsafe fn remove(&mut self, data: &G::EntryType) -> bool {
+        // SAFETY: The safety requirements are the same as `remove_internal`.
+        unsafe { self.remove_internal(data) }.is_some()
+    }
+
+    /// Removes the given entry, returning the pointer to it that was stored on the list.
+    ///
+    /// Returns `None` if the entry isn't on this list.
+    ///
+    /// # Safety
+    ///
+    /// Callers must ensure that `data` is either on this list or in no list.
+    pub(crate) unsafe fn remove_internal(
+        &mut self,
+        data: &G::EntryType,
+    ) -> Option<NonNull<G::EntryType>> {
+        let mut prev = None;
+        let mut cur = self.head;
+        while let Some(c) = cur {
+            if ptr::addr_eq(c.as_ptr(), data) {
+                // SAFETY: `c` is on the list and `prev` is its predecessor.
+                unsafe { self.unlink(prev, c) };
+                return Some(c);
+            }
+            prev = cur;
+            // SAFETY: `c` is on the list, so its links are owned by it.
+            cur = unsafe { *Self::next_of(c) };
+        }
+        None
+    }
+
+    /// Get and Remove the first element of the list.
+    pub fn pop_front(&mut self) -> Option<NonNull<G::EntryType>> {
+        let head = self.head?;
+        // SAFETY: The head is on the list and has no predecessor.
+        unsafe { self.unlink(None, head) };
+        Some(head)
+    }
+
+    /// Just Get and not remove the first element of the list.
+    pub fn front(&self) -> Option<NonNull<G::EntryType>> {
+        self.head
+    }
+
+    /// Returns a mut cursor starting on the first element of the list.
+    pub fn cursor_front_mut(&mut self) -> CursorMut<'_, G> {
+        CursorMut::new(self)
+    }
+}
+
+impl<G: GetSLinks> Default for RawSList<G> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// SAFETY: The list is itself can be safely sent to other threads but we restrict it to being `Send`
+// only when its entries are also `Send`.
+unsafe impl<G: GetSLinks> Send for RawSList<G> where G::EntryType: Send {}
+
+// SAFETY: The list is itself usable from other threads via references but we restrict it to being
+// `Sync` only when its entries are also `Sync`.
+unsafe impl<G: GetSLinks> Sync for RawSList<G> where G::EntryType: Sync {}
+
+/// A list cursor that allows traversing a singly linked list and inspecting & removing elements.
+pub struct CursorMut<'a, G: GetSLinks> {
+    prev: Option<NonNull<G::EntryType>>,
+    cur: Option<NonNull<G::EntryType>>,
+    list: &'a mut RawSList<G>,
+}
+
+impl<'a, G: GetSLinks> CursorMut<'a, G> {
+    fn new(list: &'a mut RawSList<G>) -> Self {
+        Self {
+            prev: None,
+            cur: list.head,
+            list,
+        }
+    }
+
+    /// Returns the element the cursor is currently positioned on.
+    ///
+    /// Only shared references are handed out, as other entries on the list hold pointers to the
+    /// same objects; use interior mutability to modify them.
+    pub fn current(&self) -> Option<&G::EntryType> {
+        let cur = self.cur?;
+        // SAFETY: Objects must be kept alive while on the list.
+        Some(unsafe { cur.as_ref() })
+    }
+
+    /// Removes the entry the cursor is pointing to and advances the cursor to the next entry. It
+    /// returns a raw pointer to the removed element (if one is removed).
+    pub fn remove_current(&mut self) -> Option<NonNull<G::EntryType>> {
+        let cur = self.cur?;
+        // SAFETY: `cur` is on the list, so its links are owned by it.
+        self.cur = unsafe { *RawSList::<G>::next_of(cur) };
+        // SAFETY: `cur` is on the list and `prev` is its predecessor.
+        unsafe { self.list.unlink(self.prev, cur) };
+        Some(cur)
+    }
+
+    /// Returns the element immediately after the one the cursor is positioned on.
+    pub fn peek_next(&self) -> Option<&G::EntryType> {
+        // SAFETY: `cur` is on the list, so its links are owned by it.
+        let next = unsafe { *RawSList::<G>::next_of(self.cur?) };
+        // SAFETY: Objects must be kept alive while on the list.
+        Some(unsafe { next?.as_ref() })
+    }
+
+    /// Moves the cursor to the next element.
+    pub fn move_next(&mut self) {
+        if let Some(cur) = self.cur {
+            self.prev = Some(cur);
+            // SAFETY: `cur` is on the list, so its links are owned by it.
+            self.cur = unsafe { *RawSList::<G>::next_of(cur) };
+        }
+    }
+}
+
+impl<'a, G: GetSLinks> iter::IntoIterator for &'a RawSList<G> {
+    type Item = &'a G::EntryType;
+    type IntoIter = Iterator<'a, G>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// An iterator for the singly linked list.
+pub struct Iterator<'a, G: GetSLinks> {
+    cur: Option<NonNull<G::EntryType>>,
+    _p: core::marker::PhantomData<&'a RawSList<G>>,
+}
+
+impl<G: GetSLinks> Iterator<'_, G> {
+    const fn new(cur: Option<NonNull<G::EntryType>>) -> Self {
+        Self {
+            cur,
+            _p: core::marker::PhantomData,
+        }
+    }
+}
+
+impl<'a, G: GetSLinks> iter::Iterator for Iterator<'a, G> {
+    type Item = &'a G::EntryType;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let cur = self.cur?;
+        // SAFETY: We have a shared ref to the list, so the links can't change.
+        self.cur = unsafe { *RawSList::<G>::next_of(cur) };
+        // SAFETY: Objects must be kept alive while on the list.
+        Some(unsafe { &*cur.as_ptr() })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate alloc;
+    use alloc::{boxed::Box, vec::Vec};
+
+    use super::{GetSLinks, RawSList, SLinks};
+
+    struct Example {
+        links: SLinks<Self>,
+    }
+
+    // SAFETY: This is the only adapter that uses `Example::links`.
+    impl GetSLinks for Example {
+        type EntryType = Self;
+        fn get_links(obj: &Self) -> &SLinks<Self> {
+            &obj.links
+        }
+    }
+
+    #[allow(clippy::vec_box)]
+    fn build_vector(size: usize) -> Vec<Box<Example>> {
+        (0..size)
+            .map(|_| {
+                Box::new(Example {
+                    links: SLinks::new(),
+                })
+            })
+            .collect()
+    }
+
+    #[track_caller]
+    fn assert_list_contents(v: &[&Example], list: &RawSList<Example>) {
+        let mut count = 0;
+        for (i, e) in list.iter().enumerate() {
+            assert!(core::ptr::eq(e, v[i]));
+            count += 1;
+        }
+        assert_eq!(count, v.len());
+    }
+
+    fn build_list(v: &[Box<Example>]) -> RawSList<Example> {
+        let mut list = RawSList::new();
+        for e in v.iter().rev() {
+            // SAFETY: The entry was allocated above, it's not in any lists yet, is never moved,
+            // and outlives the list.
+            unsafe { list.push_front(&**e) };
+        }
+        list
+    }
+
+    #[test]
+    fn test_push_front() {
+        const MAX: usize = 10;
+        let v = build_vector(MAX);
+        let mut list = RawSList::<Example>::new();
+
+        for n in 1..=MAX {
+            // SAFETY: The entry was allocated above, it's not in any lists yet, is never moved,
+            // and outlives the list.
+            assert!(unsafe { list.push_front(&v[MAX - n]) });
+            let expected: Vec<_> = v[MAX - n..].iter().map(|e| &**e).collect();
+            assert_list_contents(&expected, &list);
+        }
+        // SAFETY: The entry is on this list.
+        assert!(!unsafe { list.push_front(&v[0]) });
+    }
+
+    #[test]
+    fn test_insert_remove() {
+        for i in 0..5 {
+            let v = build_vector(5);
+            let extra = Box::new(Example {
+                links: SLinks::new(),
+            });
+            let mut list = build_list(&v);
+
+            // SAFETY: The i-th element is on the list, and `extra` outlives it.
+            assert!(unsafe { list.insert_after(&v[i], &extra) });
+            let mut expected: Vec<_> = v.iter().map(|e| &**e).collect();
+            expected.insert(i + 1, &extra);
+            assert_list_contents(&expected, &list);
+
+            // SAFETY: The i-th element is on the list.
+            assert!(unsafe { list.remove(&v[i]) });
+            // SAFETY: The i-th element is on no list.
+            assert!(!unsafe { list.remove(&v[i]) });
+            expected.remove(i);
+            assert_list_contents(&expected, &list);
+        }
+    }
+
+    #[test]
+    fn test_cursor() {
+        let v = build_vector(6);
+        let mut list = build_list(&v);
+
+        // Remove every other element.
+        let mut cursor = list.cursor_front_mut();
+        while cursor.current().is_some() {
+            assert!(cursor.remove_current().is_some());
+            cursor.move_next();
+        }
+        let expected: Vec<_> = v.iter().skip(1).step_by(2).map(|e| &**e).collect();
+        assert_list_contents(&expected, &list);
+
+        let cursor = list.cursor_front_mut();
+        assert!(core::ptr::eq(cursor.peek_next().unwrap(), &*v[3]));
+
+        while list.pop_front().is_some() {}
+        assert!(list.is_empty());
+    }
+}
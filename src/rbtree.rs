@@ -0,0 +1,1109 @@
+// SPDX-License-Identifier: GPL-2.0
+
+//! Intrusive red-black trees.
+//!
+//! Ordered counterpart of [`crate::List`], in the spirit of the `rbtree` module of Rust for Linux:
+//! entries are ordered by the key returned by [`KeyOf`], and use their own [`RbLinks`] and the
+//! same wrappers as [`List`](crate::List).
+
+#[cfg(feature = "alloc")]
+use alloc::{boxed::Box, sync::Arc};
+use core::{
+    borrow::Borrow,
+    iter,
+    marker::PhantomData,
+    pin::Pin,
+    ptr::{self, NonNull},
+};
+
+use crate::{
+    linked_list::Wrapper,
+    sync::{loom_const_fn, AtomicBool, Ordering, UnsafeCell},
+    KeyOf,
+};
+
+/// A descriptor of tree elements.
+///
+/// It describes the type of tree elements and provides a function to determine how to get the
+/// links to be used on a tree.
+pub trait GetRbLinks {
+    /// The type of the entries in the tree.
+    type EntryType: ?Sized;
+
+    /// Returns the links to be used when linking an entry within a tree.
+    fn get_links(data: &Self::EntryType) -> &RbLinks<Self::EntryType>;
+}
+
+/// A descriptor of wrapped tree elements.
+pub trait GetRbLinksWrapped: GetRbLinks {
+    /// Specifies which wrapper (e.g., `Box` and `Arc`) wraps the tree entries.
+    type Wrapped: Wrapper<Self::EntryType>;
+}
+
+#[cfg(feature = "alloc")]
+impl<T: ?Sized> GetRbLinksWrapped for Box<T>
+where
+    Box<T>: GetRbLinks,
+{
+    type Wrapped = Box<<Box<T> as GetRbLinks>::EntryType>;
+}
+
+#[cfg(feature = "alloc")]
+impl<T: GetRbLinks + ?Sized> GetRbLinks for Box<T> {
+    type EntryType = T::EntryType;
+
+    #[inline]
+    fn get_links(data: &Self::EntryType) -> &RbLinks<Self::EntryType> {
+        <T as GetRbLinks>::get_links(data)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T: ?Sized> GetRbLinksWrapped for Arc<T>
+where
+    Arc<T>: GetRbLinks,
+{
+    type Wrapped = Arc<<Arc<T> as GetRbLinks>::EntryType>;
+}
+
+#[cfg(feature = "alloc")]
+impl<T: GetRbLinks + ?Sized> GetRbLinks for Arc<T> {
+    type EntryType = T::EntryType;
+
+    #[inline]
+    fn get_links(data: &Self::EntryType) -> &RbLinks<Self::EntryType> {
+        <T as GetRbLinks>::get_links(data)
+    }
+}
+
+impl<T: GetRbLinks + ?Sized> GetRbLinks for &T {
+    type EntryType = T::EntryType;
+
+    #[inline]
+    fn get_links(data: &Self::EntryType) -> &RbLinks<Self::EntryType> {
+        <T as GetRbLinks>::get_links(data)
+    }
+}
+
+impl<'a, T: ?Sized> GetRbLinksWrapped for &'a T
+where
+    &'a T: GetRbLinks,
+{
+    type Wrapped = &'a <&'a T as GetRbLinks>::EntryType;
+}
+
+impl<P: GetRbLinks> GetRbLinks for Pin<P> {
+    type EntryType = P::EntryType;
+
+    #[inline]
+    fn get_links(data: &Self::EntryType) -> &RbLinks<Self::EntryType> {
+        <P as GetRbLinks>::get_links(data)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T: ?Sized> GetRbLinksWrapped for Pin<Box<T>>
+where
+    Box<T>: GetRbLinks,
+{
+    type Wrapped = Pin<Box<<Box<T> as GetRbLinks>::EntryType>>;
+}
+
+#[cfg(feature = "alloc")]
+impl<T: ?Sized> GetRbLinksWrapped for Pin<Arc<T>>
+where
+    Arc<T>: GetRbLinks,
+{
+    type Wrapped = Pin<Arc<<Arc<T> as GetRbLinks>::EntryType>>;
+}
+
+impl<'a, T: ?Sized> GetRbLinksWrapped for Pin<&'a T>
+where
+    &'a T: GetRbLinks,
+{
+    type Wrapped = Pin<&'a <&'a T as GetRbLinks>::EntryType>;
+}
+
+/// The links used to link an object on a red-black tree.
+///
+/// Instances of this type are usually embedded in structures and returned in calls to
+/// [`GetRbLinks::get_links`].
+pub struct RbLinks<T: ?Sized> {
+    inserted: AtomicBool,
+    node: UnsafeCell<RbNode<T>>,
+}
+
+// SAFETY: `RbLinks` can be safely sent to other threads but we restrict it to being `Send` only
+// when the tree entries it points to are also `Send`.
+unsafe impl<T: ?Sized> Send for RbLinks<T> {}
+
+// SAFETY: `RbLinks` is usable from other threads via references but we restrict it to being `Sync`
+// only when the tree entries it points to are also `Sync`.
+unsafe impl<T: ?Sized> Sync for RbLinks<T> {}
+
+impl<T: ?Sized> RbLinks<T> {
+    loom_const_fn! {
+        /// Constructs a new [`RbLinks`] instance that isn't inserted on any trees yet.
+        pub fn new() -> Self {
+            Self {
+                inserted: AtomicBool::new(false),
+                node: UnsafeCell::new(RbNode {
+                    parent: None,
+                    left: None,
+                    right: None,
+                    red: false,
+                }),
+            }
+        }
+    }
+
+    fn acquire_for_insertion(&self) -> bool {
+        self.inserted
+            .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_ok()
+    }
+
+    fn release_after_removal(&self) {
+        self.inserted.store(false, Ordering::Release);
+    }
+}
+
+impl<T: ?Sized> Default for RbLinks<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+struct RbNode<T: ?Sized> {
+    parent: Option<NonNull<T>>,
+    left: Option<NonNull<T>>,
+    right: Option<NonNull<T>>,
+    red: bool,
+}
+
+type Link<T> = Option<NonNull<T>>;
+
+fn same<T: ?Sized>(a: Link<T>, b: Link<T>) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => ptr::addr_eq(a.as_ptr(), b.as_ptr()),
+        (a, b) => a.is_none() && b.is_none(),
+    }
+}
+
+/// An intrusive red-black tree of wrapped entries, ordered by their keys.
+///
+/// Several entries may have the same key; they are kept in insertion order. Insertion, removal
+/// and lookups take logarithmic time.
+///
+/// # Invariants
+///
+/// The entries form a red-black tree through the nodes in their [`RbLinks`], rooted at `root`, and
+/// ordered by key. The links of objects added to a tree are owned by the tree.
+pub struct RBTree<G: GetRbLinksWrapped, K: ?Sized> {
+    root: Link<G::EntryType>,
+    len: usize,
+    _key: PhantomData<fn(&K)>,
+}
+
+impl<G, K> RBTree<G, K>
+where
+    G: GetRbLinksWrapped,
+    G::EntryType: KeyOf<K>,
+    K: Ord + ?Sized,
+{
+    /// Constructs a new empty tree.
+    pub const fn new() -> Self {
+        Self {
+            root: None,
+            len: 0,
+            _key: PhantomData,
+        }
+    }
+
+    /// Returns the number of entries in the tree.
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns whether the tree is empty.
+    pub const fn is_empty(&self) -> bool {
+        self.root.is_none()
+    }
+
+    /// Returns the node of `data`.
+    ///
+    /// # Safety
+    ///
+    /// `data` must be valid and its links must be owned by the caller.
+    unsafe fn node(data: NonNull<G::EntryType>) -> *mut RbNode<G::EntryType> {
+        // SAFETY: `data` is valid by the safety requirements.
+        G::get_links(unsafe { data.as_ref() }).node.get()
+    }
+
+    /// Returns the key of `data`.
+    ///
+    /// # Safety
+    ///
+    /// `data` must be valid for the returned lifetime.
+    unsafe fn key<'b>(data: NonNull<G::EntryType>) -> &'b K
+    where
+        G::EntryType: 'b,
+    {
+        // SAFETY: `data` is valid by the safety requirements.
+        unsafe { data.as_ref() }.key_of()
+    }
+
+    fn is_red(data: Link<G::EntryType>) -> bool {
+        // SAFETY: Entries on the tree are valid and their links are owned by it.
+        data.is_some_and(|d| unsafe { (*Self::node(d)).red })
+    }
+
+    fn set_red(data: Link<G::EntryType>, red: bool) {
+        if let Some(d) = data {
+            // SAFETY: Entries on the tree are valid and their links are owned by it.
+            unsafe { (*Self::node(d)).red = red };
+        }
+    }
+
+    /// Returns the leftmost entry of the subtree rooted at `data`.
+    ///
+    /// # Safety
+    ///
+    /// `data` must be on the tree.
+    unsafe fn leftmost(mut data: NonNull<G::EntryType>) -> NonNull<G::EntryType> {
+        // SAFETY: The subtree is on the tree.
+        while let Some(left) = unsafe { (*Self::node(data)).left } {
+            data = left;
+        }
+        data
+    }
+
+    /// Returns the rightmost entry of the subtree rooted at `data`.
+    ///
+    /// # Safety
+    ///
+    /// `data` must be on the tree.
+    unsafe fn rightmost(mut data: NonNull<G::EntryType>) -> NonNull<G::EntryType> {
+        // SAFETY: The subtree is on the tree.
+        while let Some(right) = unsafe { (*Self::node(data)).right } {
+            data = right;
+        }
+        data
+    }
+
+    /// Returns the entry that follows `data` in key order.
+    ///
+    /// # Safety
+    ///
+    /// `data` must be on the tree.
+    unsafe fn next_of(data: NonNull<G::EntryType>) -> Link<G::EntryType> {
+        // SAFETY: `data` and its ancestors are on the tree.
+        unsafe {
+            if let Some(right) = (*Self::node(data)).right {
+                return Some(Self::leftmost(right));
+            }
+            let mut cur = data;
+            while let Some(parent) = (*Self::node(cur)).parent {
+                if same((*Self::node(parent)).left, Some(cur)) {
+                    return Some(parent);
+                }
+                cur = parent;
+            }
+            None
+        }
+    }
+
+    /// Returns the entry that precedes `data` in key order.
+    ///
+    /// # Safety
+    ///
+    /// `data` must be on the tree.
+    unsafe fn prev_of(data: NonNull<G::EntryType>) -> Link<G::EntryType> {
+        // SAFETY: `data` and its ancestors are on the tree.
+        unsafe {
+            if let Some(left) = (*Self::node(data)).left {
+                return Some(Self::rightmost(left));
+            }
+            let mut cur = data;
+            while let Some(parent) = (*Self::node(cur)).parent {
+                if same((*Self::node(parent)).right, Some(cur)) {
+                    return Some(parent);
+                }
+                cur = parent;
+            }
+            None
+        }
+    }
+
+    /// Makes `new` take the place of `old` as the child of `parent`, or as the root.
+    ///
+    /// # Safety
+    ///
+    /// `old` must be a child of `parent`, or the root if `parent` is `None`.
+    unsafe fn replace_child(
+        &mut self,
+        parent: Link<G::EntryType>,
+        old: NonNull<G::EntryType>,
+        new: Link<G::EntryType>,
+    ) {
+        match parent {
+            None => self.root = new,
+            // SAFETY: `parent` is on the tree.
+            Some(parent) => unsafe {
+                let parent = Self::node(parent);
+                if same((*parent).left, Some(old)) {
+                    (*parent).left = new;
+                } else {
+                    (*parent).right = new;
+                }
+            },
+        }
+    }
+
+    /// Rotates the subtree rooted at `x` to the left, so that its right child replaces it.
+    ///
+    /// # Safety
+    ///
+    /// `x` must be on the tree and have a right child.
+    unsafe fn rotate_left(&mut self, x: NonNull<G::EntryType>) {
+        // SAFETY: `x` and its neighbours are on the tree.
+        unsafe {
+            let xn = Self::node(x);
+            let y = (*xn).right.unwrap();
+            let yn = Self::node(y);
+            (*xn).right = (*yn).left;
+            if let Some(b) = (*yn).left {
+                (*Self::node(b)).parent = Some(x);
+            }
+            (*yn).parent = (*xn).parent;
+            self.replace_child((*xn).parent, x, Some(y));
+            (*yn).left = Some(x);
+            (*xn).parent = Some(y);
+        }
+    }
+
+    /// Rotates the subtree rooted at `x` to the right, so that its left child replaces it.
+    ///
+    /// # Safety
+    ///
+    /// `x` must be on the tree and have a left child.
+    unsafe fn rotate_right(&mut self, x: NonNull<G::EntryType>) {
+        // SAFETY: `x` and its neighbours are on the tree.
+        unsafe {
+            let xn = Self::node(x);
+            let y = (*xn).left.unwrap();
+            let yn = Self::node(y);
+            (*xn).left = (*yn).right;
+            if let Some(b) = (*yn).right {
+                (*Self::node(b)).parent = Some(x);
+            }
+            (*yn).parent = (*xn).parent;
+            self.replace_child((*xn).parent, x, Some(y));
+            (*yn).right = Some(x);
+            (*xn).parent = Some(y);
+        }
+    }
+
+    /// Adds the given object to the tree, after the entries with the same key.
+    ///
+    /// It is dropped if it's already on this (or another) tree; this can happen for
+    /// reference-counted objects, so dropping means decrementing the reference count.
+    pub fn insert(&mut self, data: G::Wrapped) {
+        let ptr = data.into_pointer();
+        // SAFETY: We took ownership of the entry, so it is valid.
+        let links = G::get_links(unsafe { ptr.as_ref() });
+        if !links.acquire_for_insertion() {
+            // If insertion failed, rebuild object so that it can be freed.
+            // SAFETY: We just called `into_pointer` above.
+            unsafe { G::Wrapped::from_pointer(ptr) };
+            return;
+        }
+
+        // SAFETY: We took ownership of the entry, and the entries on the tree are valid and
+        // owned by it.
+        unsafe {
+            let key = Self::key(ptr);
+            let mut parent = None;
+            let mut cur = self.root;
+            let mut left = false;
+            while let Some(c) = cur {
+                parent = cur;
+                left = key < Self::key(c);
+                cur = if left {
+                    (*Self::node(c)).left
+                } else {
+                    (*Self::node(c)).right
+                };
+            }
+
+            *links.node.get() = RbNode {
+                parent,
+                left: None,
+                right: None,
+                red: true,
+            };
+            match parent {
+                None => self.root = Some(ptr),
+                Some(p) if left => (*Self::node(p)).left = Some(ptr),
+                Some(p) => (*Self::node(p)).right = Some(ptr),
+            }
+            self.insert_fixup(ptr);
+        }
+        self.len += 1;
+    }
+
+    /// Restores the red-black properties after `z` was inserted as a red leaf.
+    ///
+    /// # Safety
+    ///
+    /// `z` must be on the tree.
+    unsafe fn insert_fixup(&mut self, mut z: NonNull<G::EntryType>) {
+        // SAFETY: `z` and its ancestors are on the tree. A red parent is never the root, so it
+        // has a parent.
+        unsafe {
+            while let Some(p) = (*Self::node(z)).parent.filter(|&p| Self::is_red(Some(p))) {
+                let g = (*Self::node(p)).parent.unwrap();
+                let parent_is_left = same((*Self::node(g)).left, Some(p));
+                let uncle = if parent_is_left {
+                    (*Self::node(g)).right
+                } else {
+                    (*Self::node(g)).left
+                };
+                if Self::is_red(uncle) {
+                    Self::set_red(Some(p), false);
+                    Self::set_red(uncle, false);
+                    Self::set_red(Some(g), true);
+                    z = g;
+                    continue;
+                }
+
+                let mut p = p;
+                if parent_is_left {
+                    if same((*Self::node(p)).right, Some(z)) {
+                        z = p;
+                        self.rotate_left(z);
+                        p = (*Self::node(z)).parent.unwrap();
+                    }
+                    Self::set_red(Some(p), false);
+                    Self::set_red(Some(g), true);
+                    self.rotate_right(g);
+                } else {
+                    if same((*Self::node(p)).left, Some(z)) {
+                        z = p;
+                        self.rotate_right(z);
+                        p = (*Self::node(z)).parent.unwrap();
+                    }
+                    Self::set_red(Some(p), false);
+                    Self::set_red(Some(g), true);
+                    self.rotate_left(g);
+                }
+            }
+        }
+        Self::set_red(self.root, false);
+    }
+
+    /// Makes the subtree rooted at `v` take the place of the one rooted at `u`.
+    ///
+    /// # Safety
+    ///
+    /// `u` must be on the tree.
+    unsafe fn transplant(&mut self, u: NonNull<G::EntryType>, v: Link<G::EntryType>) {
+        // SAFETY: `u` and `v` are on the tree.
+        unsafe {
+            let parent = (*Self::node(u)).parent;
+            self.replace_child(parent, u, v);
+            if let Some(v) = v {
+                (*Self::node(v)).parent = parent;
+            }
+        }
+    }
+
+    /// Unlinks `z` from the tree, returning the pointer to it that was stored on the tree.
+    ///
+    /// # Safety
+    ///
+    /// `z` must be on the tree.
+    unsafe fn unlink(&mut self, z: &G::EntryType) -> NonNull<G::EntryType> {
+        // SAFETY: `z` and its neighbours are on the tree.
+        unsafe {
+            let zn = G::get_links(z).node.get();
+            let stored = match (*zn).parent {
+                None => self.root,
+                Some(p) if same((*Self::node(p)).left, Some(NonNull::from(z))) => {
+                    (*Self::node(p)).left
+                }
+                Some(p) => (*Self::node(p)).right,
+            }
+            .unwrap();
+
+            let mut removed_red = (*zn).red;
+            let x;
+            let x_parent;
+            match ((*zn).left, (*zn).right) {
+                (None, right) => {
+                    x = right;
+                    x_parent = (*zn).parent;
+                    self.transplant(stored, right);
+                }
+                (left, None) => {
+                    x = left;
+                    x_parent = (*zn).parent;
+                    self.transplant(stored, left);
+                }
+                (Some(left), Some(right)) => {
+                    let y = Self::leftmost(right);
+                    let yn = Self::node(y);
+                    removed_red = (*yn).red;
+                    x = (*yn).right;
+                    if same((*yn).parent, Some(stored)) {
+                        x_parent = Some(y);
+                    } else {
+                        x_parent = (*yn).parent;
+                        self.transplant(y, (*yn).right);
+                        (*yn).right = Some(right);
+                        (*Self::node(right)).parent = Some(y);
+                    }
+                    self.transplant(stored, Some(y));
+                    (*yn).left = Some(left);
+                    (*Self::node(left)).parent = Some(y);
+                    (*yn).red = (*zn).red;
+                }
+            }
+            if !removed_red {
+                self.remove_fixup(x, x_parent);
+            }
+
+            *zn = RbNode {
+                parent: None,
+                left: None,
+                right: None,
+                red: false,
+            };
+            stored
+        }
+    }
+
+    /// Restores the red-black properties after a black entry was removed above `x`.
+    ///
+    /// # Safety
+    ///
+    /// `x` must be on the tree or `None`, and `parent` must be its parent.
+    unsafe fn remove_fixup(&mut self, mut x: Link<G::EntryType>, mut parent: Link<G::EntryType>) {
+        // SAFETY: `x`, `parent` and their neighbours are on the tree. The sibling of `x` exists
+        // because the path through it has at least one more black entry.
+        unsafe {
+            while !same(x, self.root) && !Self::is_red(x) {
+                let p = parent.unwrap();
+                let pn = Self::node(p);
+                if same((*pn).left, x) {
+                    let mut w = (*pn).right.unwrap();
+                    if Self::is_red(Some(w)) {
+                        Self::set_red(Some(w), false);
+                        Self::set_red(Some(p), true);
+                        self.rotate_left(p);
+                        w = (*pn).right.unwrap();
+                    }
+                    let wn = Self::node(w);
+                    if !Self::is_red((*wn).left) && !Self::is_red((*wn).right) {
+                        Self::set_red(Some(w), true);
+                        x = Some(p);
+                        parent = (*pn).parent;
+                    } else {
+                        if !Self::is_red((*wn).right) {
+                            Self::set_red((*wn).left, false);
+                            Self::set_red(Some(w), true);
+                            self.rotate_right(w);
+                            w = (*pn).right.unwrap();
+                        }
+                        let wn = Self::node(w);
+                        (*wn).red = (*pn).red;
+                        Self::set_red(Some(p), false);
+                        Self::set_red((*wn).right, false);
+                        self.rotate_left(p);
+                        x = self.root;
+                        parent = None;
+                    }
+                } else {
+                    let mut w = (*pn).left.unwrap();
+                    if Self::is_red(Some(w)) {
+                        Self::set_red(Some(w), false);
+                        Self::set_red(Some(p), true);
+                        self.rotate_right(p);
+                        w = (*pn).left.unwrap();
+                    }
+                    let wn = Self::node(w);
+                    if !Self::is_red((*wn).left) && !Self::is_red((*wn).right) {
+                        Self::set_red(Some(w), true);
+                        x = Some(p);
+                        parent = (*pn).parent;
+                    } else {
+                        if !Self::is_red((*wn).left) {
+                            Self::set_red((*wn).right, false);
+                            Self::set_red(Some(w), true);
+                            self.rotate_left(w);
+                            w = (*pn).left.unwrap();
+                        }
+                        let wn = Self::node(w);
+                        (*wn).red = (*pn).red;
+                        Self::set_red(Some(p), false);
+                        Self::set_red((*wn).left, false);
+                        self.rotate_right(p);
+                        x = self.root;
+                        parent = None;
+                    }
+                }
+            }
+        }
+        Self::set_red(x, false);
+    }
+
+    /// Returns whether `data` is on this tree, given that it is on this tree or in no tree.
+    fn contains(&self, data: &G::EntryType) -> bool {
+        // SAFETY: The links are owned by this tree if `data` is in it, and not modified by anyone
+        // otherwise.
+        let parent = unsafe { (*G::get_links(data).node.get()).parent };
+        parent.is_some() || same(self.root, Some(NonNull::from(data)))
+    }
+
+    /// Removes the given entry.
+    ///
+    /// # Safety
+    ///
+    /// Callers must ensure that `data` is either on this tree or in no tree. It being on another
+    /// tree leads to memory unsafety.
+    pub unsafe fn remove(&mut self, data: &G::Wrapped) -> Option<G::Wrapped> {
+        let entry = Wrapper::as_ref(data);
+        if !self.contains(entry) {
+            return None;
+        }
+        // SAFETY: The entry is on this tree.
+        let ptr = unsafe { self.unlink(entry) };
+        G::get_links(entry).release_after_removal();
+        self.len -= 1;
+        // SAFETY: Elements on the tree were inserted after a call to `into_pointer`.
+        Some(unsafe { G::Wrapped::from_pointer(ptr) })
+    }
+
+    /// Removes the first entry in key order and returns it.
+    ///
+    /// Returns `None` if the tree is empty.
+    pub fn pop_first(&mut self) -> Option<G::Wrapped> {
+        // SAFETY: The root is on the tree.
+        let first = unsafe { Self::leftmost(self.root?) };
+        // SAFETY: The first entry is on the tree.
+        Some(unsafe { self.remove_ptr(first) })
+    }
+
+    /// Removes the entry pointed to by `ptr`.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be on the tree.
+    unsafe fn remove_ptr(&mut self, ptr: NonNull<G::EntryType>) -> G::Wrapped {
+        // SAFETY: The entry is on the tree, so it is valid.
+        let entry = unsafe { ptr.as_ref() };
+        // SAFETY: The entry is on the tree.
+        let ptr = unsafe { self.unlink(entry) };
+        G::get_links(entry).release_after_removal();
+        self.len -= 1;
+        // SAFETY: Elements on the tree were inserted after a call to `into_pointer`.
+        unsafe { G::Wrapped::from_pointer(ptr) }
+    }
+
+    /// Returns the first entry whose key isn't less than `key`.
+    fn lower_bound_ptr<Q: Ord + ?Sized>(&self, key: &Q) -> Link<G::EntryType>
+    where
+        K: Borrow<Q>,
+    {
+        let mut found = None;
+        let mut cur = self.root;
+        while let Some(c) = cur {
+            // SAFETY: Entries on the tree are valid and their links are owned by it.
+            unsafe {
+                if Self::key(c).borrow() < key {
+                    cur = (*Self::node(c)).right;
+                } else {
+                    found = cur;
+                    cur = (*Self::node(c)).left;
+                }
+            }
+        }
+        found
+    }
+
+    /// Returns the first entry whose key isn't less than `key`.
+    pub fn lower_bound<Q: Ord + ?Sized>(&self, key: &Q) -> Option<&G::EntryType>
+    where
+        K: Borrow<Q>,
+    {
+        // SAFETY: Objects must be kept alive while on the tree.
+        self.lower_bound_ptr(key).map(|p| unsafe { p.as_ref() })
+    }
+
+    /// Returns the first entry with the given key, if there is one.
+    pub fn find<Q: Ord + ?Sized>(&self, key: &Q) -> Option<&G::EntryType>
+    where
+        K: Borrow<Q>,
+    {
+        self.lower_bound(key).filter(|e| e.key_of().borrow() == key)
+    }
+
+    /// Returns an iterator over the entries in key order.
+    pub fn iter(&self) -> Iter<'_, G, K> {
+        // SAFETY: The root is on the tree.
+        let bounds = self
+            .root
+            .map(|root| unsafe { (Self::leftmost(root), Self::rightmost(root)) });
+        Iter {
+            front: bounds.map(|b| b.0),
+            back: bounds.map(|b| b.1),
+            remaining: self.len,
+            _tree: PhantomData,
+        }
+    }
+
+    /// Returns a mutable cursor starting on the first entry in key order.
+    pub fn cursor_front_mut(&mut self) -> CursorMut<'_, G, K> {
+        // SAFETY: The root is on the tree.
+        let cur = self.root.map(|root| unsafe { Self::leftmost(root) });
+        CursorMut { tree: self, cur }
+    }
+
+    /// Returns a mutable cursor starting on the first entry whose key isn't less than `key`.
+    pub fn cursor_lower_bound_mut<Q: Ord + ?Sized>(&mut self, key: &Q) -> CursorMut<'_, G, K>
+    where
+        K: Borrow<Q>,
+    {
+        let cur = self.lower_bound_ptr(key);
+        CursorMut { tree: self, cur }
+    }
+}
+
+impl<G, K> Default for RBTree<G, K>
+where
+    G: GetRbLinksWrapped,
+    G::EntryType: KeyOf<K>,
+    K: Ord + ?Sized,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<G: GetRbLinksWrapped, K: ?Sized> Drop for RBTree<G, K> {
+    fn drop(&mut self) {
+        // Free the entries in post-order, without rebalancing.
+        let mut cur = self.root.take();
+        while let Some(c) = cur {
+            let links = G::get_links(
+                // SAFETY: Entries on the tree are valid.
+                unsafe { c.as_ref() },
+            );
+            // SAFETY: The links are owned by the tree.
+            let node = unsafe { &mut *links.node.get() };
+            if let Some(left) = node.left.take() {
+                cur = Some(left);
+            } else if let Some(right) = node.right.take() {
+                cur = Some(right);
+            } else {
+                cur = node.parent.take();
+                links.release_after_removal();
+                // SAFETY: The parent no longer points to the entry, so this is the stored pointer
+                // that was just detached, which came from `into_pointer`.
+                unsafe { G::Wrapped::from_pointer(c) };
+            }
+        }
+    }
+}
+
+// SAFETY: The tree owns the wrapped entries, so it can be sent to other threads as long as they
+// can be sent too.
+unsafe impl<G: GetRbLinksWrapped, K: ?Sized> Send for RBTree<G, K> where G::Wrapped: Send {}
+
+// SAFETY: Shared references to the tree only hand out shared references to its entries, which is
+// as much as shared references to the wrappers allow.
+unsafe impl<G: GetRbLinksWrapped, K: ?Sized> Sync for RBTree<G, K> where G::Wrapped: Sync {}
+
+/// An iterator over the entries of a tree in key order.
+pub struct Iter<'a, G: GetRbLinksWrapped, K: ?Sized> {
+    front: Link<G::EntryType>,
+    back: Link<G::EntryType>,
+    remaining: usize,
+    _tree: PhantomData<&'a RBTree<G, K>>,
+}
+
+impl<'a, G, K> iter::Iterator for Iter<'a, G, K>
+where
+    G: GetRbLinksWrapped,
+    G::EntryType: KeyOf<K>,
+    K: Ord + ?Sized,
+{
+    type Item = &'a G::EntryType;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        let cur = self.front?;
+        self.remaining -= 1;
+        // SAFETY: We have a shared ref to the tree, so it can't change and its entries are valid.
+        unsafe {
+            self.front = RBTree::<G, K>::next_of(cur);
+            Some(cur.as_ref())
+        }
+    }
+}
+
+impl<G, K> iter::DoubleEndedIterator for Iter<'_, G, K>
+where
+    G: GetRbLinksWrapped,
+    G::EntryType: KeyOf<K>,
+    K: Ord + ?Sized,
+{
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        let cur = self.back?;
+        self.remaining -= 1;
+        // SAFETY: We have a shared ref to the tree, so it can't change and its entries are valid.
+        unsafe {
+            self.back = RBTree::<G, K>::prev_of(cur);
+            Some(cur.as_ref())
+        }
+    }
+}
+
+impl<'a, G, K> iter::IntoIterator for &'a RBTree<G, K>
+where
+    G: GetRbLinksWrapped,
+    G::EntryType: KeyOf<K>,
+    K: Ord + ?Sized,
+{
+    type Item = &'a G::EntryType;
+    type IntoIter = Iter<'a, G, K>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// A tree cursor that allows traversing a tree in key order and inspecting & removing entries.
+///
+/// When it moves past either end of the tree, it points to no entry; moving it again then wraps
+/// around to the other end.
+pub struct CursorMut<'a, G: GetRbLinksWrapped, K: ?Sized> {
+    tree: &'a mut RBTree<G, K>,
+    cur: Link<G::EntryType>,
+}
+
+impl<G, K> CursorMut<'_, G, K>
+where
+    G: GetRbLinksWrapped,
+    G::EntryType: KeyOf<K>,
+    K: Ord + ?Sized,
+{
+    fn next(&self) -> Link<G::EntryType> {
+        match self.cur {
+            // SAFETY: The current entry is on the tree.
+            Some(cur) => unsafe { RBTree::<G, K>::next_of(cur) },
+            // SAFETY: The root is on the tree.
+            None => self
+                .tree
+                .root
+                .map(|r| unsafe { RBTree::<G, K>::leftmost(r) }),
+        }
+    }
+
+    fn prev(&self) -> Link<G::EntryType> {
+        match self.cur {
+            // SAFETY: The current entry is on the tree.
+            Some(cur) => unsafe { RBTree::<G, K>::prev_of(cur) },
+            // SAFETY: The root is on the tree.
+            None => self
+                .tree
+                .root
+                .map(|r| unsafe { RBTree::<G, K>::rightmost(r) }),
+        }
+    }
+
+    /// Returns the entry the cursor is currently positioned on.
+    ///
+    /// Entries are only accessible through shared references while they are on the tree, as
+    /// other entries hold pointers to them.
+    pub fn current(&self) -> Option<&G::EntryType> {
+        // SAFETY: Objects must be kept alive while on the tree.
+        self.cur.map(|c| unsafe { c.as_ref() })
+    }
+
+    /// Returns the entry immediately after the one the cursor is positioned on.
+    pub fn peek_next(&self) -> Option<&G::EntryType> {
+        // SAFETY: Objects must be kept alive while on the tree.
+        self.next().map(|c| unsafe { c.as_ref() })
+    }
+
+    /// Returns the entry immediately before the one the cursor is positioned on.
+    pub fn peek_prev(&self) -> Option<&G::EntryType> {
+        // SAFETY: Objects must be kept alive while on the tree.
+        self.prev().map(|c| unsafe { c.as_ref() })
+    }
+
+    /// Moves the cursor to the next entry.
+    pub fn move_next(&mut self) {
+        self.cur = self.next();
+    }
+
+    /// Moves the cursor to the previous entry.
+    pub fn move_prev(&mut self) {
+        self.cur = self.prev();
+    }
+
+    /// Removes the entry the cursor is currently positioned on.
+    ///
+    /// After removal, it advances the cursor to the next entry.
+    pub fn remove_current(&mut self) -> Option<G::Wrapped> {
+        let cur = self.cur?;
+        self.cur = self.next();
+        // SAFETY: The entry is on the tree.
+        Some(unsafe { self.tree.remove_ptr(cur) })
+    }
+}
+
+#[cfg(all(test, feature = "alloc", not(loom)))]
+mod tests {
+    use core::ptr::NonNull;
+    use std::{sync::Arc, vec::Vec};
+
+    use super::{GetRbLinks, RBTree, RbLinks};
+    use crate::KeyOf;
+
+    struct Area {
+        start: u64,
+        links: RbLinks<Self>,
+    }
+
+    impl GetRbLinks for Area {
+        type EntryType = Self;
+        fn get_links(obj: &Self) -> &RbLinks<Self> {
+            &obj.links
+        }
+    }
+
+    impl KeyOf<u64> for Area {
+        fn key_of(&self) -> &u64 {
+            &self.start
+        }
+    }
+
+    type Tree = RBTree<Arc<Area>, u64>;
+
+    fn new_area(start: u64) -> Arc<Area> {
+        Arc::new(Area {
+            start,
+            links: RbLinks::new(),
+        })
+    }
+
+    /// Checks the red-black properties of the subtree rooted at `data`, and returns its black
+    /// height.
+    fn check_subtree(data: Option<NonNull<Area>>, parent: Option<NonNull<Area>>) -> usize {
+        let Some(d) = data else {
+            return 1;
+        };
+        // SAFETY: The entries are on the tree, which isn't modified while it's checked.
+        let node = unsafe { &*Tree::node(d) };
+        assert!(super::same(node.parent, parent));
+        if node.red {
+            assert!(!Tree::is_red(node.left) && !Tree::is_red(node.right));
+        }
+        let left = check_subtree(node.left, data);
+        let right = check_subtree(node.right, data);
+        assert_eq!(left, right);
+        left + usize::from(!node.red)
+    }
+
+    #[track_caller]
+    fn assert_tree(tree: &Tree, expected: &[u64]) {
+        assert!(!Tree::is_red(tree.root));
+        check_subtree(tree.root, None);
+        assert_eq!(tree.len(), expected.len());
+        let keys: Vec<_> = tree.iter().map(|a| a.start).collect();
+        assert_eq!(keys, expected);
+        let keys: Vec<_> = tree.iter().rev().map(|a| a.start).collect();
+        assert!(keys.iter().eq(expected.iter().rev()));
+    }
+
+    #[test]
+    fn test_insert_remove() {
+        // A pseudo-random sequence of keys, with some duplicates.
+        let areas: Vec<_> = (0..200u64).map(|i| new_area(i * 7919 % 150)).collect();
+        let mut tree = Tree::new();
+        let mut expected = Vec::new();
+        for a in &areas {
+            tree.insert(a.clone());
+            expected.push(a.start);
+        }
+        // Already on the tree, so this is dropped.
+        tree.insert(areas[0].clone());
+        expected.sort_unstable();
+        assert_tree(&tree, &expected);
+
+        for a in areas.iter().step_by(3) {
+            // SAFETY: The area is on this tree.
+            assert!(unsafe { tree.remove(a) }.is_some());
+            // SAFETY: The area is in no tree.
+            assert!(unsafe { tree.remove(a) }.is_none());
+            let pos = expected.binary_search(&a.start).unwrap();
+            expected.remove(pos);
+            assert_tree(&tree, &expected);
+        }
+
+        while let Some(first) = tree.pop_first() {
+            assert_eq!(first.start, expected.remove(0));
+        }
+        assert!(tree.is_empty());
+        assert!(areas.iter().all(|a| Arc::strong_count(a) == 1));
+    }
+
+    #[test]
+    fn test_lookup_and_cursor() {
+        let mut tree = Tree::new();
+        for start in [40, 10, 30, 20, 30, 50] {
+            tree.insert(new_area(start));
+        }
+        assert_eq!(tree.find(&30).unwrap().start, 30);
+        assert!(tree.find(&35).is_none());
+        assert_eq!(tree.lower_bound(&35).unwrap().start, 40);
+        assert_eq!(tree.lower_bound(&0).unwrap().start, 10);
+        assert!(tree.lower_bound(&51).is_none());
+
+        // Remove the entries in [20, 40) with a cursor.
+        let mut cursor = tree.cursor_lower_bound_mut(&20);
+        assert_eq!(cursor.peek_prev().unwrap().start, 10);
+        while cursor.current().is_some_and(|a| a.start < 40) {
+            assert!(cursor.remove_current().is_some());
+        }
+        assert_eq!(cursor.current().unwrap().start, 40);
+        cursor.move_next();
+        cursor.move_next();
+        assert!(cursor.current().is_none());
+        // The cursor wraps around past the ends.
+        cursor.move_next();
+        assert_eq!(cursor.current().unwrap().start, 10);
+        assert_tree(&tree, &[10, 40, 50]);
+    }
+
+    #[test]
+    fn test_drop() {
+        let areas: Vec<_> = (0..20).map(new_area).collect();
+        let mut tree = Tree::new();
+        for a in &areas {
+            tree.insert(a.clone());
+        }
+        drop(tree);
+        assert!(areas.iter().all(|a| Arc::strong_count(a) == 1));
+        // The links were released, so the areas can be inserted again.
+        let mut tree = Tree::new();
+        tree.insert(areas[0].clone());
+        assert_tree(&tree, &[0]);
+    }
+}
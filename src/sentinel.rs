@@ -0,0 +1,481 @@
+// SPDX-License-Identifier: GPL-2.0
+
+//! Sentinel-head lists.
+//!
+//! Based on the `list_head` design in linux/include/linux/list.h: the list head is itself a
+//! link in the ring, so insertion and removal never need to special-case the first, last or only
+//! element.
+
+use core::{
+    iter,
+    marker::{PhantomData, PhantomPinned},
+    pin::Pin,
+    ptr::{self, NonNull},
+};
+
+use crate::{
+    raw_list::ListEntry,
+    sync::{loom_const_fn, UnsafeCell},
+    GetLinksOffset, Links,
+};
+
+type EntryPtr<A> = NonNull<<A as GetLinksOffset>::EntryType>;
+
+/// A linked list whose head is a sentinel link embedded in the list itself.
+///
+/// Entries are described by a [`GetLinksOffset`] implementation, so the sentinel can be
+/// addressed as if it were the links of an (imaginary) entry. This makes every ring operation a
+/// fixed sequence of pointer updates.
+///
+/// The list is self-referential, so all mutating operations take `Pin<&mut Self>`. Its ring is
+/// initialised when the first entry is added to it, as that can only be done once it is pinned.
+/// Entries still on the list when it is dropped are unlinked from it.
+///
+/// # Invariants
+///
+/// Once initialised, the sentinel and the links of all entries on the list form a ring in which
+/// all `next` and `prev` pointers are `Some`. The links of objects added to a list are owned by
+/// the list.
+pub struct SentinelList<A: GetLinksOffset> {
+    head: Links<A::EntryType>,
+    _pin: PhantomPinned,
+}
+
+impl<A: GetLinksOffset> SentinelList<A> {
+    loom_const_fn! {
+        /// Constructs a new empty list.
+        pub fn new() -> Self {
+            Self {
+                head: Links::new(),
+                _pin: PhantomPinned,
+            }
+        }
+    }
+
+    /// Returns the pointer that stands for the sentinel in the ring.
+    ///
+    /// It points `LINKS_OFFSET` bytes before the sentinel links, so it must never be dereferenced
+    /// as an entry, only passed to [`Self::entry_of`].
+    #[inline(always)]
+    fn sentinel(head: *const Links<A::EntryType>) -> NonNull<A::EntryType> {
+        let ptr = head.wrapping_byte_sub(A::LINKS_OFFSET) as *mut A::EntryType;
+        // SAFETY: `head` is a valid pointer to the sentinel and objects never straddle the end of
+        // the address space, so the pointer isn't null.
+        unsafe { NonNull::new_unchecked(ptr) }
+    }
+
+    /// Returns a pointer to the links of `ptr`, which is either an entry or the sentinel.
+    #[inline(always)]
+    fn links_of(ptr: NonNull<A::EntryType>) -> *const Links<A::EntryType> {
+        ptr.as_ptr().wrapping_byte_add(A::LINKS_OFFSET) as *const _
+    }
+
+    /// Returns a pointer to the list entry of `ptr`, which is either an entry or the sentinel.
+    #[inline(always)]
+    fn entry_of(ptr: NonNull<A::EntryType>) -> *mut ListEntry<A::EntryType> {
+        let links = Self::links_of(ptr);
+        // SAFETY: `links` points to valid links, either of an entry or of the sentinel.
+        unsafe { UnsafeCell::raw_get(&raw const (*links).entry) }
+    }
+
+    /// Returns the sentinel of a pinned list, initialising its ring first if needed.
+    ///
+    /// The ring can only be initialised once the list is pinned, as it points to the list itself.
+    /// Until then, `next` and `prev` are `None` and the list is treated as empty.
+    #[inline(always)]
+    fn pinned_sentinel(self: Pin<&mut Self>) -> NonNull<A::EntryType> {
+        // SAFETY: The list is not moved out of the pinned reference.
+        let this = unsafe { self.get_unchecked_mut() };
+        let sentinel = Self::sentinel(&raw const this.head);
+        let entry = this.head.entry.get_mut();
+        if entry.next.is_none() {
+            Self::init_ring(entry, sentinel);
+        }
+        sentinel
+    }
+
+    /// Makes the sentinel point to itself, which is the ring of an empty list.
+    #[cold]
+    fn init_ring(entry: &mut ListEntry<A::EntryType>, sentinel: NonNull<A::EntryType>) {
+        entry.next = Some(sentinel);
+        entry.prev = Some(sentinel);
+    }
+
+    /// Returns the sentinel, the first and the last entries (or the sentinel if the list is empty)
+    /// of a list that may not have been initialised yet.
+    fn ends(&self) -> (EntryPtr<A>, EntryPtr<A>, EntryPtr<A>) {
+        let sentinel = Self::sentinel(&raw const self.head);
+        // SAFETY: The list cannot change while we have a shared reference to it.
+        let entry = unsafe { &*self.head.entry.get() };
+        (
+            sentinel,
+            entry.next.unwrap_or(sentinel),
+            entry.prev.unwrap_or(sentinel),
+        )
+    }
+
+    /// Returns whether the list is empty.
+    pub fn is_empty(&self) -> bool {
+        let (sentinel, front, _) = self.ends();
+        ptr::addr_eq(front.as_ptr(), sentinel.as_ptr())
+    }
+
+    /// Links `new` between the consecutive links `prev` and `next`.
+    ///
+    /// # Safety
+    ///
+    /// `prev` and `next` must be consecutive on the ring, and the links of `new` must be owned by
+    /// the list.
+    #[inline(always)]
+    unsafe fn link_between(
+        new: NonNull<A::EntryType>,
+        prev: NonNull<A::EntryType>,
+        next: NonNull<A::EntryType>,
+    ) {
+        // SAFETY: All pointers are valid by the safety requirements.
+        unsafe {
+            (*Self::entry_of(next)).prev = Some(new);
+            let new_entry = &mut *Self::entry_of(new);
+            new_entry.next = Some(next);
+            new_entry.prev = Some(prev);
+            (*Self::entry_of(prev)).next = Some(new);
+        }
+    }
+
+    /// Inserts `new` after `existing`, where `existing` may be the sentinel.
+    ///
+    /// # Safety
+    ///
+    /// `existing` must be on the ring and `new` must be valid while on the list.
+    #[inline(always)]
+    unsafe fn insert_after_ptr(
+        existing: NonNull<A::EntryType>,
+        new: NonNull<A::EntryType>,
+    ) -> bool {
+        // SAFETY: `new` is valid by the safety requirements.
+        let links = unsafe { &*Self::links_of(new) };
+        if !links.acquire_for_insertion() {
+            // Nothing to do if already inserted.
+            return false;
+        }
+
+        // SAFETY: `existing` is on the ring, so its `next` is `Some` by the type invariants.
+        unsafe {
+            let next = (*Self::entry_of(existing)).next.unwrap_unchecked();
+            Self::link_between(new, existing, next);
+        }
+        true
+    }
+
+    /// Adds the given object to the end (back) of the list.
+    ///
+    /// Returns `false` if the object is already on a list.
+    ///
+    /// # Safety
+    ///
+    /// The caller must ensure the validity of the reference while it is on the list.
+    pub unsafe fn push_back(self: Pin<&mut Self>, new: &A::EntryType) -> bool {
+        let sentinel = self.pinned_sentinel();
+        // SAFETY: The sentinel is initialised, so its `prev` is `Some`, and the last entry is on
+        // the ring.
+        unsafe {
+            let back = (*Self::entry_of(sentinel)).prev.unwrap_unchecked();
+            Self::insert_after_ptr(back, NonNull::from(new))
+        }
+    }
+
+    /// Adds the given object to the first (front) of the list.
+    ///
+    /// Returns `false` if the object is already on a list.
+    ///
+    /// # Safety
+    ///
+    /// The caller must ensure the validity of the reference while it is on the list.
+    pub unsafe fn push_front(self: Pin<&mut Self>, new: &A::EntryType) -> bool {
+        let sentinel = self.pinned_sentinel();
+        // SAFETY: The sentinel is initialised and on the ring, and `new` is valid by the safety
+        // requirements.
+        unsafe { Self::insert_after_ptr(sentinel, NonNull::from(new)) }
+    }
+
+    /// Inserts the given object after `existing`.
+    ///
+    /// Returns `false` if the object is already on a list.
+    ///
+    /// # Safety
+    ///
+    /// Callers must ensure that `existing` points to a valid entry that is on the list, and the
+    /// validity of `new` while it is on the list.
+    pub unsafe fn insert_after(
+        self: Pin<&mut Self>,
+        existing: &A::EntryType,
+        new: &A::EntryType,
+    ) -> bool {
+        // SAFETY: `existing` is on the list by the safety requirements.
+        unsafe { Self::insert_after_ptr(NonNull::from(existing), NonNull::from(new)) }
+    }
+
+    /// Removes the given entry.
+    ///
+    /// Returns `false` if the entry is not on a list.
+    ///
+    /// # Safety
+    ///
+    /// Callers must ensure that `data` is either on this list or in no list. It being on another
+    /// list leads to memory unsafety.
+    pub unsafe fn remove(self: Pin<&mut Self>, data: &A::EntryType) -> bool {
+        // SAFETY: `data` is valid as it is on this list or on no list.
+        unsafe { Self::unlink(NonNull::from(data)) }
+    }
+
+    /// Unlinks the given entry from the ring it is on.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be valid, and either on this list or in no list.
+    #[inline(always)]
+    unsafe fn unlink(ptr: NonNull<A::EntryType>) -> bool {
+        // SAFETY: The links are either owned by this list or unused.
+        let entry = unsafe { &mut *Self::entry_of(ptr) };
+        if entry.is_unlinked() {
+            // Nothing to do if the entry is not on the list.
+            return false;
+        }
+
+        // SAFETY: The entry is on the ring, so its neighbours are valid and `Some`.
+        unsafe {
+            let next = entry.next.unwrap_unchecked();
+            let prev = entry.prev.unwrap_unchecked();
+            (*Self::entry_of(prev)).next = Some(next);
+            (*Self::entry_of(next)).prev = Some(prev);
+        }
+
+        // Reset the links of the element we're removing so that we know it's not on any list.
+        entry.reset(ptr);
+        // SAFETY: `ptr` is valid by the safety requirements.
+        unsafe { &*Self::links_of(ptr) }.release_after_removal();
+        true
+    }
+
+    /// Removes the first element of the list and returns it.
+    pub fn pop_front(self: Pin<&mut Self>) -> Option<NonNull<A::EntryType>> {
+        let (sentinel, front, _) = self.ends();
+        if ptr::addr_eq(front.as_ptr(), sentinel.as_ptr()) {
+            return None;
+        }
+        // SAFETY: `front` is on this list.
+        unsafe { Self::unlink(front) };
+        Some(front)
+    }
+
+    /// Returns an iterator for the list starting at the first entry.
+    pub fn iter(&self) -> SentinelIter<'_, A> {
+        let (sentinel, front, back) = self.ends();
+        SentinelIter {
+            sentinel,
+            front,
+            back,
+            _p: PhantomData,
+        }
+    }
+}
+
+impl<A: GetLinksOffset> Default for SentinelList<A> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<A: GetLinksOffset> Drop for SentinelList<A> {
+    fn drop(&mut self) {
+        // SAFETY: The list is pinned while in use, so it isn't moved before it is dropped.
+        let mut this = unsafe { Pin::new_unchecked(self) };
+        // Unlink the remaining entries, so that their links don't point to the freed sentinel
+        // and they can be added to other lists.
+        while this.as_mut().pop_front().is_some() {}
+    }
+}
+
+// SAFETY: The list is itself can be safely sent to other threads but we restrict it to being `Send`
+// only when its entries are also `Send`.
+unsafe impl<A: GetLinksOffset> Send for SentinelList<A> where A::EntryType: Send {}
+
+// SAFETY: The list is itself usable from other threads via references but we restrict it to being
+// `Sync` only when its entries are also `Sync`.
+unsafe impl<A: GetLinksOffset> Sync for SentinelList<A> where A::EntryType: Sync {}
+
+/// An iterator for a [`SentinelList`].
+pub struct SentinelIter<'a, A: GetLinksOffset> {
+    sentinel: NonNull<A::EntryType>,
+    front: NonNull<A::EntryType>,
+    back: NonNull<A::EntryType>,
+    _p: PhantomData<&'a A::EntryType>,
+}
+
+impl<A: GetLinksOffset> SentinelIter<'_, A> {
+    fn finish(&mut self) {
+        self.front = self.sentinel;
+        self.back = self.sentinel;
+    }
+}
+
+impl<'a, A: GetLinksOffset> iter::Iterator for SentinelIter<'a, A> {
+    type Item = &'a A::EntryType;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let cur = self.front;
+        if ptr::addr_eq(cur.as_ptr(), self.sentinel.as_ptr()) {
+            return None;
+        }
+        if ptr::addr_eq(cur.as_ptr(), self.back.as_ptr()) {
+            self.finish();
+        } else {
+            // SAFETY: `cur` is on the list, so its `next` is `Some` by the type invariants.
+            self.front = unsafe { (*SentinelList::<A>::entry_of(cur)).next.unwrap_unchecked() };
+        }
+        // SAFETY: Objects must be kept alive while on the list.
+        Some(unsafe { cur.as_ref() })
+    }
+}
+
+impl<A: GetLinksOffset> iter::DoubleEndedIterator for SentinelIter<'_, A> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let cur = self.back;
+        if ptr::addr_eq(cur.as_ptr(), self.sentinel.as_ptr()) {
+            return None;
+        }
+        if ptr::addr_eq(cur.as_ptr(), self.front.as_ptr()) {
+            self.finish();
+        } else {
+            // SAFETY: `cur` is on the list, so its `prev` is `Some` by the type invariants.
+            self.back = unsafe { (*SentinelList::<A>::entry_of(cur)).prev.unwrap_unchecked() };
+        }
+        // SAFETY: Objects must be kept alive while on the list.
+        Some(unsafe { cur.as_ref() })
+    }
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    extern crate alloc;
+    use alloc::{boxed::Box, vec::Vec};
+    use core::{mem::offset_of, pin::pin};
+
+    use super::SentinelList;
+    use crate::{GetLinksOffset, Links};
+
+    struct Example {
+        value: usize,
+        links: Links<Self>,
+    }
+
+    // SAFETY: `links` is a `Links<Example>` field of `Example`.
+    unsafe impl GetLinksOffset for Example {
+        type EntryType = Self;
+        const LINKS_OFFSET: usize = offset_of!(Example, links);
+    }
+
+    #[allow(clippy::vec_box)]
+    fn build_vector(size: usize) -> Vec<Box<Example>> {
+        (0..size)
+            .map(|value| {
+                Box::new(Example {
+                    value,
+                    links: Links::new(),
+                })
+            })
+            .collect()
+    }
+
+    #[track_caller]
+    fn assert_list_contents(list: &SentinelList<Example>, expected: &[usize]) {
+        let forward: Vec<_> = list.iter().map(|e| e.value).collect();
+        assert_eq!(forward, expected);
+        let mut backward: Vec<_> = list.iter().rev().map(|e| e.value).collect();
+        backward.reverse();
+        assert_eq!(backward, expected);
+        assert_eq!(list.is_empty(), expected.is_empty());
+    }
+
+    #[test]
+    fn test_push() {
+        let v = build_vector(4);
+        let mut list = pin!(SentinelList::<Example>::new());
+        assert_list_contents(&list, &[]);
+
+        // SAFETY: The entries outlive the list and are not moved.
+        unsafe {
+            assert!(list.as_mut().push_back(&v[1]));
+            assert!(list.as_mut().push_front(&v[0]));
+            assert!(list.as_mut().push_back(&v[3]));
+            assert!(list.as_mut().insert_after(&v[1], &v[2]));
+            assert!(!list.as_mut().push_back(&v[2]));
+        }
+        assert_list_contents(&list, &[0, 1, 2, 3]);
+
+        let mut iter = list.iter();
+        assert_eq!(iter.next().unwrap().value, 0);
+        assert_eq!(iter.next_back().unwrap().value, 3);
+        assert_eq!(iter.next().unwrap().value, 1);
+        assert_eq!(iter.next_back().unwrap().value, 2);
+        assert!(iter.next().is_none());
+        assert!(iter.next_back().is_none());
+    }
+
+    #[test]
+    fn test_remove() {
+        for i in 0..5 {
+            let v = build_vector(5);
+            let mut list = pin!(SentinelList::<Example>::new());
+            for e in &v {
+                // SAFETY: The entries outlive the list and are not moved.
+                unsafe { list.as_mut().push_back(e) };
+            }
+
+            // SAFETY: The entry is on this list.
+            assert!(unsafe { list.as_mut().remove(&v[i]) });
+            // SAFETY: The entry is on no list.
+            assert!(!unsafe { list.as_mut().remove(&v[i]) });
+
+            let expected: Vec<_> = (0..5).filter(|&j| j != i).collect();
+            assert_list_contents(&list, &expected);
+        }
+    }
+
+    #[test]
+    fn test_pop_front() {
+        let v = build_vector(3);
+        let mut list = pin!(SentinelList::<Example>::new());
+        assert!(list.as_mut().pop_front().is_none());
+        for e in &v {
+            // SAFETY: The entries outlive the list and are not moved.
+            unsafe { list.as_mut().push_back(e) };
+        }
+        for e in &v {
+            let front = list.as_mut().pop_front().unwrap();
+            assert!(core::ptr::eq(front.as_ptr(), &**e));
+        }
+        assert!(list.as_mut().pop_front().is_none());
+        assert_list_contents(&list, &[]);
+    }
+
+    #[test]
+    fn test_drop_unlinks_entries() {
+        let v = build_vector(3);
+        {
+            let mut list = pin!(SentinelList::<Example>::new());
+            for e in &v {
+                // SAFETY: The entries outlive the list and are not moved.
+                unsafe { list.as_mut().push_back(e) };
+            }
+        }
+
+        // The entries were unlinked when the list was dropped, so they can be added to another.
+        let mut list = pin!(SentinelList::<Example>::new());
+        for e in &v {
+            // SAFETY: The entries outlive the list and are not moved.
+            assert!(unsafe { list.as_mut().push_back(e) });
+        }
+        assert_list_contents(&list, &[0, 1, 2]);
+    }
+}
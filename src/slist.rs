@@ -0,0 +1,351 @@
+// SPDX-License-Identifier: GPL-2.0
+
+//! Singly linked lists.
+//!
+//! Singly linked counterpart of [`crate::List`].
+
+#[cfg(feature = "alloc")]
+use alloc::{boxed::Box, sync::Arc};
+use core::{iter, pin::Pin};
+
+use crate::{linked_list::Wrapper, raw_slist, raw_slist::RawSList, GetSLinks, SLinks};
+
+/// A descriptor of wrapped singly linked list elements.
+pub trait GetSLinksWrapped: GetSLinks {
+    /// Specifies which wrapper (e.g., `Box` and `Arc`) wraps the list entries.
+    type Wrapped: Wrapper<Self::EntryType>;
+}
+
+#[cfg(feature = "alloc")]
+impl<T: ?Sized> GetSLinksWrapped for Box<T>
+where
+    Box<T>: GetSLinks,
+{
+    type Wrapped = Box<<Box<T> as GetSLinks>::EntryType>;
+}
+
+#[cfg(feature = "alloc")]
+impl<T: GetSLinks + ?Sized> GetSLinks for Box<T> {
+    type EntryType = T::EntryType;
+
+    #[inline]
+    fn get_links(data: &Self::EntryType) -> &SLinks<Self::EntryType> {
+        <T as GetSLinks>::get_links(data)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T: ?Sized> GetSLinksWrapped for Arc<T>
+where
+    Arc<T>: GetSLinks,
+{
+    type Wrapped = Arc<<Arc<T> as GetSLinks>::EntryType>;
+}
+
+#[cfg(feature = "alloc")]
+impl<T: GetSLinks + ?Sized> GetSLinks for Arc<T> {
+    type EntryType = T::EntryType;
+
+    #[inline]
+    fn get_links(data: &Self::EntryType) -> &SLinks<Self::EntryType> {
+        <T as GetSLinks>::get_links(data)
+    }
+}
+
+impl<T: GetSLinks + ?Sized> GetSLinks for &T {
+    type EntryType = T::EntryType;
+
+    #[inline]
+    fn get_links(data: &Self::EntryType) -> &SLinks<Self::EntryType> {
+        <T as GetSLinks>::get_links(data)
+    }
+}
+
+impl<'a, T: ?Sized> GetSLinksWrapped for &'a T
+where
+    &'a T: GetSLinks,
+{
+    type Wrapped = &'a <&'a T as GetSLinks>::EntryType;
+}
+
+impl<P: GetSLinks> GetSLinks for Pin<P> {
+    type EntryType = P::EntryType;
+
+    #[inline]
+    fn get_links(data: &Self::EntryType) -> &SLinks<Self::EntryType> {
+        <P as GetSLinks>::get_links(data)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T: ?Sized> GetSLinksWrapped for Pin<Box<T>>
+where
+    Box<T>: GetSLinks,
+{
+    type Wrapped = Pin<Box<<Box<T> as GetSLinks>::EntryType>>;
+}
+
+#[cfg(feature = "alloc")]
+impl<T: ?Sized> GetSLinksWrapped for Pin<Arc<T>>
+where
+    Arc<T>: GetSLinks,
+{
+    type Wrapped = Pin<Arc<<Arc<T> as GetSLinks>::EntryType>>;
+}
+
+impl<'a, T: ?Sized> GetSLinksWrapped for Pin<&'a T>
+where
+    &'a T: GetSLinks,
+{
+    type Wrapped = Pin<&'a <&'a T as GetSLinks>::EntryType>;
+}
+
+/// A singly linked list.
+///
+/// Elements in the list are wrapped and ownership is transferred to the list while the element is
+/// in the list.
+///
+/// Compared to [`List`](crate::List), entries only need one pointer in their links, at the cost
+/// of only supporting efficient operations at the front.
+pub struct SList<G: GetSLinksWrapped> {
+    list: RawSList<G>,
+}
+
+impl<G: GetSLinksWrapped> SList<G> {
+    /// Constructs a new empty singly linked list.
+    pub const fn new() -> Self {
+        Self {
+            list: RawSList::new(),
+        }
+    }
+
+    /// Returns an iterator for the list starting at the first entry.
+    pub fn iter(&self) -> Iterator<'_, G> {
+        Iterator::new(self)
+    }
+
+    /// Returns whether the list is empty.
+    pub const fn is_empty(&self) -> bool {
+        self.list.is_empty()
+    }
+
+    /// Adds the given object to the first (front) of the list.
+    ///
+    /// It is dropped if it's already on this (or another) list; this can happen for
+    /// reference-counted objects, so dropping means decrementing the reference count.
+    pub fn push_front(&mut self, data: G::Wrapped) {
+        let ptr = data.into_pointer();
+
+        // SAFETY: We took ownership of the entry, so it is safe to insert it.
+        if !unsafe { self.list.push_front_internal(ptr) } {
+            // If insertion failed, rebuild object so that it can be freed.
+            // SAFETY: We just called `into_pointer` above.
+            unsafe { G::Wrapped::from_pointer(ptr) };
+        }
+    }
+
+    /// Inserts the given object after `existing`.
+    ///
+    /// It is dropped if it's already on this (or another) list; this can happen for
+    /// reference-counted objects, so dropping means decrementing the reference count.
+    ///
+    /// # Safety
+    ///
+    /// Callers must ensure that `existing` points to a valid entry that is on the list.
+    pub unsafe fn insert_after(&mut self, existing: &G::Wrapped, data: G::Wrapped) {
+        let ptr = data.into_pointer();
+        let entry = Wrapper::as_ref(existing);
+        if unsafe { !self.list.insert_after_internal(entry, ptr) } {
+            // If insertion failed, rebuild object so that it can be freed.
+            unsafe { G::Wrapped::from_pointer(ptr) };
+        }
+    }
+
+    /// Removes the given entry.
+    ///
+    /// This walks the list to find the predecessor of `data`, so it takes linear time.
+    ///
+    /// # Safety
+    ///
+    /// Callers must ensure that `data` is either on this list or in no list. It being on another
+    /// list leads to memory unsafety.
+    pub unsafe fn remove(&mut self, data: &G::Wrapped) -> Option<G::Wrapped> {
+        // SAFETY: The safety requirements are the same as `RawSList::remove_internal`.
+        let ptr = unsafe { self.list.remove_internal(Wrapper::as_ref(data)) }?;
+        // SAFETY: The pointer is the one stored on the list, which came from `into_pointer`.
+        Some(unsafe { G::Wrapped::from_pointer(ptr) })
+    }
+
+    /// Removes the element currently at the front of the list and returns it.
+    ///
+    /// Returns `None` if the list is empty.
+    pub fn pop_front(&mut self) -> Option<G::Wrapped> {
+        let front = self.list.pop_front()?;
+        // SAFETY: Elements on the list were inserted after a call to `into_pointer `.
+        Some(unsafe { G::Wrapped::from_pointer(front) })
+    }
+
+    /// Returns a mutable cursor starting on the first (front) element of the list.
+    pub fn cursor_front_mut(&mut self) -> CursorMut<'_, G> {
+        CursorMut::new(self.list.cursor_front_mut())
+    }
+}
+
+impl<G: GetSLinksWrapped> Default for SList<G> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<G: GetSLinksWrapped> Drop for SList<G> {
+    fn drop(&mut self) {
+        while self.pop_front().is_some() {}
+    }
+}
+
+// SAFETY: The list owns its wrapped entries and drops them on whichever thread it is dropped, so
+// it can be sent to other threads only when they can. `RawSList` alone would allow it for entries
+// that are `Send`, even when the wrappers, e.g. `Rc`, are not.
+unsafe impl<G: GetSLinksWrapped> Send for SList<G> where G::Wrapped: Send {}
+
+// SAFETY: Shared references to the list only hand out shared references to its entries, which is
+// as much as shared references to the wrappers allow.
+unsafe impl<G: GetSLinksWrapped> Sync for SList<G> where G::Wrapped: Sync {}
+
+/// A list cursor that allows traversing a singly linked list and inspecting & mutating elements.
+pub struct CursorMut<'a, G: GetSLinksWrapped> {
+    cursor: raw_slist::CursorMut<'a, G>,
+}
+
+impl<'a, G: GetSLinksWrapped> CursorMut<'a, G> {
+    const fn new(cursor: raw_slist::CursorMut<'a, G>) -> Self {
+        Self { cursor }
+    }
+
+    /// Returns the element the cursor is currently positioned on.
+    ///
+    /// Entries are only accessible through shared references while they are on the list, as
+    /// other entries hold pointers to them.
+    pub fn current(&self) -> Option<&G::EntryType> {
+        self.cursor.current()
+    }
+
+    /// Removes the element the cursor is currently positioned on.
+    ///
+    /// After removal, it advances the cursor to the next element.
+    pub fn remove_current(&mut self) -> Option<G::Wrapped> {
+        let ptr = self.cursor.remove_current()?;
+
+        // SAFETY: Elements on the list were inserted after a call to `into_pointer `.
+        Some(unsafe { G::Wrapped::from_pointer(ptr) })
+    }
+
+    /// Returns the element immediately after the one the cursor is positioned on.
+    pub fn peek_next(&self) -> Option<&G::EntryType> {
+        self.cursor.peek_next()
+    }
+
+    /// Moves the cursor to the next element.
+    pub fn move_next(&mut self) {
+        self.cursor.move_next();
+    }
+}
+
+/// An iterator for the singly linked list.
+pub struct Iterator<'a, G: GetSLinksWrapped> {
+    iter: raw_slist::Iterator<'a, G>,
+}
+
+impl<'a, G: GetSLinksWrapped> Iterator<'a, G> {
+    fn new(list: &'a SList<G>) -> Self {
+        Self {
+            iter: list.list.iter(),
+        }
+    }
+}
+
+impl<'a, G: GetSLinksWrapped> iter::Iterator for Iterator<'a, G> {
+    type Item = &'a G::EntryType;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iter.next()
+    }
+}
+
+#[cfg(all(test, feature = "alloc"))]
+mod tests {
+    use core::mem::size_of;
+    use std::sync::Arc;
+
+    use super::{GetSLinks, SLinks, SList};
+    use crate::Links;
+
+    struct Example {
+        inner: usize,
+        links: SLinks<Self>,
+    }
+
+    impl GetSLinks for Example {
+        type EntryType = Self;
+        fn get_links(obj: &Self) -> &SLinks<Self> {
+            &obj.links
+        }
+    }
+
+    fn new_example(inner: usize) -> Example {
+        Example {
+            inner,
+            links: SLinks::new(),
+        }
+    }
+
+    #[test]
+    fn test_links_size() {
+        assert_eq!(size_of::<SLinks<Example>>(), 2 * size_of::<usize>());
+        assert!(size_of::<SLinks<Example>>() < size_of::<Links<Example>>());
+    }
+
+    #[test]
+    fn test_push_pop() {
+        const MAX: usize = 10;
+        let mut list = SList::<Box<Example>>::new();
+
+        for n in 1..=MAX {
+            list.push_front(Box::new(new_example(n)));
+        }
+        let values: Vec<_> = list.iter().map(|e| e.inner).collect();
+        assert_eq!(values, (1..=MAX).rev().collect::<Vec<_>>());
+
+        for n in (1..=MAX).rev() {
+            assert_eq!(list.pop_front().unwrap().inner, n);
+        }
+        assert!(list.pop_front().is_none());
+        assert!(list.is_empty());
+    }
+
+    #[test]
+    fn test_cursor_and_remove() {
+        let shared: Vec<_> = (0..4).map(|n| Arc::new(new_example(n))).collect();
+        let mut list = SList::<Arc<Example>>::new();
+        for e in shared.iter().rev() {
+            list.push_front(e.clone());
+        }
+        // Already on the list, so this is dropped.
+        list.push_front(shared[0].clone());
+        assert_eq!(Arc::strong_count(&shared[0]), 2);
+
+        let mut cursor = list.cursor_front_mut();
+        cursor.move_next();
+        assert_eq!(cursor.peek_next().unwrap().inner, 2);
+        assert_eq!(cursor.remove_current().unwrap().inner, 1);
+        assert_eq!(cursor.current().unwrap().inner, 2);
+
+        // SAFETY: `shared[3]` is on this list.
+        assert!(unsafe { list.remove(&shared[3]) }.is_some());
+        let values: Vec<_> = list.iter().map(|e| e.inner).collect();
+        assert_eq!(values, [0, 2]);
+
+        drop(list);
+        assert!(shared.iter().all(|e| Arc::strong_count(e) == 1));
+    }
+}
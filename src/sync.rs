@@ -0,0 +1,122 @@
+// SPDX-License-Identifier: GPL-2.0
+
+//! Synchronisation primitives.
+//!
+//! When built with `--cfg loom`, the atomics used by links and lock-free structures are provided
+//! by [loom] so that they can be model-checked. Loom atomics can only be used within
+//! `loom::model`, so only the loom tests can be run with the cfg set:
+//!
+//! ```sh
+//! RUSTFLAGS="--cfg loom" cargo test --release --lib loom
+//! ```
+//!
+//! A `cfg` is used rather than a cargo feature so that `--all-features` builds, and crates that
+//! enable every feature of their dependencies, never end up with loom atomics.
+//!
+//! [loom]: https://docs.rs/loom
+
+#[cfg(not(loom))]
+pub(crate) use core::sync::atomic::{AtomicBool, AtomicPtr, Ordering};
+#[cfg(loom)]
+pub(crate) use loom::sync::atomic::{AtomicBool, AtomicPtr, Ordering};
+
+/// Defines a function that is `const` unless built with `--cfg loom`, because the
+/// constructors of loom atomics aren't `const`.
+///
+/// It is exported so that [`def_node!`](crate::def_node) can use it for the constructors of the
+/// nodes it defines. The macro itself is defined conditionally, rather than expanding to `cfg`
+/// attributes, so that the cfg is evaluated in this crate instead of the caller's.
+#[cfg(not(loom))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __loom_const_fn {
+    ($(#[$attr:meta])* $vis:vis fn $name:ident($($args:tt)*) -> $ret:ty $body:block) => {
+        $(#[$attr])*
+        $vis const fn $name($($args)*) -> $ret $body
+    };
+}
+
+/// Defines a function that is `const` unless built with `--cfg loom`, because the
+/// constructors of loom atomics aren't `const`.
+#[cfg(loom)]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __loom_const_fn {
+    ($(#[$attr:meta])* $vis:vis fn $name:ident($($args:tt)*) -> $ret:ty $body:block) => {
+        $(#[$attr])*
+        $vis fn $name($($args)*) -> $ret $body
+    };
+}
+
+pub(crate) use crate::__loom_const_fn as loom_const_fn;
+
+#[cfg(not(loom))]
+use core::cell::UnsafeCell as InnerUnsafeCell;
+#[cfg(loom)]
+use loom::cell::UnsafeCell as InnerUnsafeCell;
+
+/// A cell whose contents are accessed through raw pointers, like [`core::cell::UnsafeCell`].
+///
+/// When built with `--cfg loom`, it wraps a loom cell so that accesses to its contents are
+/// checked for data races. Loom cells only hand out pointers within closures, so every call to
+/// [`UnsafeCell::get`] is recorded as a mutable access: the returned pointer must not be used
+/// after the next synchronisation operation (such as an atomic access) of the calling thread, or
+/// accesses through it won't be checked.
+#[repr(transparent)]
+pub(crate) struct UnsafeCell<T>(InnerUnsafeCell<T>);
+
+impl<T> UnsafeCell<T> {
+    loom_const_fn! {
+        /// Constructs a new cell holding `data`.
+        pub(crate) fn new(data: T) -> Self {
+            Self(InnerUnsafeCell::new(data))
+        }
+    }
+
+    /// Returns a mutable pointer to the contents of the cell.
+    #[inline(always)]
+    pub(crate) fn get(&self) -> *mut T {
+        #[cfg(not(loom))]
+        {
+            self.0.get()
+        }
+        #[cfg(loom)]
+        {
+            self.0.with_mut(|ptr| ptr)
+        }
+    }
+
+    /// Returns a mutable reference to the contents of the cell.
+    #[inline(always)]
+    pub(crate) fn get_mut(&mut self) -> &mut T {
+        #[cfg(not(loom))]
+        {
+            self.0.get_mut()
+        }
+        #[cfg(loom)]
+        {
+            // SAFETY: The exclusive reference to the cell ensures that there are no other
+            // accesses to its contents.
+            self.0.with_mut(|ptr| unsafe { &mut *ptr })
+        }
+    }
+
+    /// Returns a mutable pointer to the contents of the cell pointed to by `this`.
+    ///
+    /// # Safety
+    ///
+    /// `this` must point to a valid cell.
+    #[inline(always)]
+    pub(crate) unsafe fn raw_get(this: *const Self) -> *mut T {
+        #[cfg(not(loom))]
+        {
+            // SAFETY: `this` is valid by the safety requirements, and no reference is created.
+            InnerUnsafeCell::raw_get(unsafe { &raw const (*this).0 })
+        }
+        #[cfg(loom)]
+        {
+            // SAFETY: `this` is valid by the safety requirements.
+            unsafe { (*this).get() }
+        }
+    }
+}
@@ -0,0 +1,417 @@
+// SPDX-License-Identifier: GPL-2.0
+
+//! Lock-protected lists.
+//!
+//! A [`List`] and the lock that protects it, so that lists can be shared between threads without
+//! wrapping them by hand.
+
+use core::{
+    cell::UnsafeCell,
+    hint,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use crate::linked_list::{CursorMut, GetLinksWrapped, List};
+
+/// A raw lock, which protects data it doesn't own.
+///
+/// # Safety
+///
+/// Implementers must provide mutual exclusion: once [`RawLock::lock`] has returned, or
+/// [`RawLock::try_lock`] has returned `true`, neither of them may succeed again until
+/// [`RawLock::unlock`] is called.
+pub unsafe trait RawLock {
+    /// An unlocked lock, used to initialise locks in constant contexts.
+    const INIT: Self;
+
+    /// Acquires the lock, waiting until it is available.
+    fn lock(&self);
+
+    /// Tries to acquire the lock without waiting, returning whether it was acquired.
+    fn try_lock(&self) -> bool;
+
+    /// Releases the lock.
+    ///
+    /// # Safety
+    ///
+    /// Callers must ensure that the lock is held in the current context.
+    unsafe fn unlock(&self);
+}
+
+/// A spin lock that doesn't depend on the standard library.
+///
+/// It doesn't disable interrupts or preemption; kernels that need that should provide their own
+/// [`RawLock`] implementation.
+pub struct RawSpinLock {
+    locked: AtomicBool,
+}
+
+impl RawSpinLock {
+    /// Constructs a new unlocked spin lock.
+    pub const fn new() -> Self {
+        Self {
+            locked: AtomicBool::new(false),
+        }
+    }
+}
+
+impl Default for RawSpinLock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// SAFETY: The lock is only acquired by swapping `locked` from `false` to `true`, which only one
+// context can do until `unlock` stores `false` again.
+unsafe impl RawLock for RawSpinLock {
+    const INIT: Self = Self::new();
+
+    fn lock(&self) {
+        // The weak exchange may fail spuriously, which only costs another iteration here.
+        while self
+            .locked
+            .compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            while self.locked.load(Ordering::Relaxed) {
+                hint::spin_loop();
+            }
+        }
+    }
+
+    fn try_lock(&self) -> bool {
+        self.locked
+            .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_ok()
+    }
+
+    unsafe fn unlock(&self) {
+        self.locked.store(false, Ordering::Release);
+    }
+}
+
+/// A blocking lock built on the standard library's [`Mutex`](std::sync::Mutex) and
+/// [`Condvar`](std::sync::Condvar).
+#[cfg(any(test, feature = "std"))]
+pub struct StdRawLock {
+    locked: std::sync::Mutex<bool>,
+    cond: std::sync::Condvar,
+}
+
+#[cfg(any(test, feature = "std"))]
+impl StdRawLock {
+    /// Constructs a new unlocked lock.
+    pub const fn new() -> Self {
+        Self {
+            locked: std::sync::Mutex::new(false),
+            cond: std::sync::Condvar::new(),
+        }
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, bool> {
+        // The state is a plain flag, so it is consistent even if a thread panicked with it.
+        self.locked.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(any(test, feature = "std"))]
+impl Default for StdRawLock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// SAFETY: `locked` is only set by the context that observed it cleared while holding the mutex,
+// and is only cleared by `unlock`.
+#[cfg(any(test, feature = "std"))]
+unsafe impl RawLock for StdRawLock {
+    const INIT: Self = Self::new();
+
+    fn lock(&self) {
+        let mut locked = self.state();
+        while *locked {
+            locked = self.cond.wait(locked).unwrap_or_else(|e| e.into_inner());
+        }
+        *locked = true;
+    }
+
+    fn try_lock(&self) -> bool {
+        let mut locked = self.state();
+        !core::mem::replace(&mut *locked, true)
+    }
+
+    unsafe fn unlock(&self) {
+        *self.state() = false;
+        self.cond.notify_one();
+    }
+}
+
+/// Releases the lock when dropped, so that it is released on unwind too.
+struct Guard<'a, L: RawLock>(&'a L);
+
+impl<L: RawLock> Drop for Guard<'_, L> {
+    fn drop(&mut self) {
+        // SAFETY: The guard is only created after the lock is acquired.
+        unsafe { self.0.unlock() };
+    }
+}
+
+/// A linked list protected by a lock.
+///
+/// All operations take a shared reference and acquire the lock for their duration, so the list
+/// can be shared between threads (e.g., in an `Arc` or a `static`).
+///
+/// The lock is not reentrant: calling methods of the same list from within the closures passed
+/// to [`SyncList::with`] or [`SyncList::with_cursor`] deadlocks.
+pub struct SyncList<G: GetLinksWrapped, L: RawLock = RawSpinLock> {
+    lock: L,
+    list: UnsafeCell<List<G>>,
+}
+
+impl<G: GetLinksWrapped, L: RawLock> SyncList<G, L> {
+    /// Constructs a new empty list.
+    pub const fn new() -> Self {
+        Self {
+            lock: L::INIT,
+            list: UnsafeCell::new(List::new()),
+        }
+    }
+
+    /// Acquires the lock and calls `f` with the list.
+    pub fn with<R>(&self, f: impl FnOnce(&mut List<G>) -> R) -> R {
+        self.lock.lock();
+        let _guard = Guard(&self.lock);
+        // SAFETY: The lock is held, so we have exclusive access to the list.
+        f(unsafe { &mut *self.list.get() })
+    }
+
+    /// Acquires the lock and calls `f` with a cursor starting on the first element of the list.
+    pub fn with_cursor<R>(&self, f: impl FnOnce(&mut CursorMut<'_, G>) -> R) -> R {
+        self.with(|list| f(&mut list.cursor_front_mut()))
+    }
+
+    /// Returns whether the list is empty.
+    pub fn is_empty(&self) -> bool {
+        self.with(|list| list.is_empty())
+    }
+
+    /// Adds the given object to the end (back) of the list.
+    ///
+    /// It is dropped if it's already on this (or another) list; this can happen for
+    /// reference-counted objects, so dropping means decrementing the reference count.
+    pub fn push_back(&self, data: G::Wrapped) {
+        self.with(|list| list.push_back(data))
+    }
+
+    /// Adds the given object to the first (front) of the list.
+    ///
+    /// It is dropped if it's already on this (or another) list; this can happen for
+    /// reference-counted objects, so dropping means decrementing the reference count.
+    pub fn push_front(&self, data: G::Wrapped) {
+        self.with(|list| list.push_front(data))
+    }
+
+    /// Removes the element currently at the front of the list and returns it.
+    ///
+    /// Returns `None` if the list is empty.
+    pub fn pop_front(&self) -> Option<G::Wrapped> {
+        self.with(|list| list.pop_front())
+    }
+
+    /// Removes the given entry.
+    ///
+    /// # Safety
+    ///
+    /// Callers must ensure that `data` is either on this list or in no list. It being on another
+    /// list leads to memory unsafety.
+    pub unsafe fn remove(&self, data: &G::Wrapped) -> Option<G::Wrapped> {
+        // SAFETY: The safety requirements are the same as `List::remove`.
+        self.with(|list| unsafe { list.remove(data) })
+    }
+
+    /// Returns a mutable reference to the list.
+    ///
+    /// No locking is needed because the mutable reference guarantees exclusive access.
+    pub fn get_mut(&mut self) -> &mut List<G> {
+        self.list.get_mut()
+    }
+}
+
+/// A locked list of statically allocated entries, which can itself be a `static`.
+///
+/// Entries are linked through `&'static` references, so neither the list nor its entries need an
+/// allocator, and the list is `Sync` as long as the entries are.
+///
+/// # Examples
+///
+/// ```
+/// use linked_list_r4l::{GetLinks, Links, StaticList};
+///
+/// struct Task {
+///     id: usize,
+///     links: Links<Task>,
+/// }
+///
+/// impl GetLinks for Task {
+///     type EntryType = Self;
+///
+///     fn get_links(task: &Self) -> &Links<Self> {
+///         &task.links
+///     }
+/// }
+///
+/// static IDLE: Task = Task { id: 0, links: Links::new() };
+/// static RUN_QUEUE: StaticList<Task> = StaticList::new();
+///
+/// RUN_QUEUE.push_back(&IDLE);
+/// assert_eq!(RUN_QUEUE.pop_front().unwrap().id, 0);
+/// ```
+pub type StaticList<T, L = RawSpinLock> = SyncList<&'static T, L>;
+
+impl<G: GetLinksWrapped, L: RawLock> Default for SyncList<G, L> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// SAFETY: The list owns the wrapped entries, so it can be sent to other threads as long as they
+// can be sent too.
+unsafe impl<G: GetLinksWrapped, L: RawLock + Send> Send for SyncList<G, L> where G::Wrapped: Send {}
+
+// SAFETY: The lock serialises all accesses to the list, which may move the wrapped entries between
+// threads, so they must be `Send`.
+unsafe impl<G: GetLinksWrapped, L: RawLock + Sync> Sync for SyncList<G, L> where G::Wrapped: Send {}
+
+#[cfg(all(test, feature = "alloc", not(loom)))]
+mod tests {
+    use std::{sync::Arc, thread, vec::Vec};
+
+    use super::{RawLock, RawSpinLock, StaticList, StdRawLock, SyncList};
+    use crate::{GetLinks, Links};
+
+    struct Example {
+        inner: usize,
+        links: Links<Self>,
+    }
+
+    impl GetLinks for Example {
+        type EntryType = Self;
+        fn get_links(obj: &Self) -> &Links<Self> {
+            &obj.links
+        }
+    }
+
+    const fn new_example(inner: usize) -> Example {
+        Example {
+            inner,
+            links: Links::new(),
+        }
+    }
+
+    fn test_concurrent<L: RawLock + Send + Sync + 'static>() {
+        const THREADS: usize = 4;
+        const PER_THREAD: usize = 1000;
+
+        let list = Arc::new(SyncList::<Box<Example>, L>::new());
+        let handles: Vec<_> = (0..THREADS)
+            .map(|t| {
+                let list = list.clone();
+                thread::spawn(move || {
+                    for i in 0..PER_THREAD {
+                        list.push_back(Box::new(new_example(t * PER_THREAD + i)));
+                        if i % 2 == 0 {
+                            assert!(list.pop_front().is_some());
+                        }
+                    }
+                })
+            })
+            .collect();
+        for h in handles {
+            h.join().unwrap();
+        }
+
+        let len = list.with(|list| list.iter().count());
+        assert_eq!(len, THREADS * PER_THREAD / 2);
+    }
+
+    #[test]
+    fn test_concurrent_spin() {
+        test_concurrent::<RawSpinLock>();
+    }
+
+    #[test]
+    fn test_concurrent_std() {
+        test_concurrent::<StdRawLock>();
+    }
+
+    #[test]
+    fn test_remove_and_cursor() {
+        let shared: Vec<_> = (0..6).map(|n| Arc::new(new_example(n))).collect();
+        let list = SyncList::<Arc<Example>, StdRawLock>::new();
+        assert!(list.is_empty());
+        for e in &shared {
+            list.push_back(e.clone());
+        }
+
+        // SAFETY: `shared[2]` is on this list.
+        assert!(unsafe { list.remove(&shared[2]) }.is_some());
+        // SAFETY: `shared[2]` is on no list.
+        assert!(unsafe { list.remove(&shared[2]) }.is_none());
+
+        // Remove odd elements with the cursor.
+        let removed = list.with_cursor(|cursor| {
+            let mut removed = 0;
+            while let Some(e) = cursor.current() {
+                if e.inner % 2 == 1 {
+                    cursor.remove_current();
+                    removed += 1;
+                } else {
+                    cursor.move_next();
+                }
+            }
+            removed
+        });
+        assert_eq!(removed, 3);
+
+        let values = list.with(|list| list.iter().map(|e| e.inner).collect::<Vec<_>>());
+        assert_eq!(values, [0, 4]);
+        drop(list);
+        assert!(shared.iter().all(|e| Arc::strong_count(e) == 1));
+    }
+
+    #[test]
+    fn test_unlock_on_panic() {
+        let list = SyncList::<Box<Example>>::new();
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            list.with(|_| panic!("boom"));
+        }));
+        assert!(result.is_err());
+        list.push_back(Box::new(new_example(0)));
+        assert!(!list.is_empty());
+    }
+
+    #[test]
+    fn test_static_list() {
+        const THREADS: usize = 4;
+
+        static NODES: [Example; THREADS] = [const { new_example(0) }; THREADS];
+        static LIST: StaticList<Example> = StaticList::new();
+
+        let handles: Vec<_> = NODES
+            .iter()
+            .map(|node| thread::spawn(move || LIST.push_front(node)))
+            .collect();
+        for h in handles {
+            h.join().unwrap();
+        }
+        // Already on the list, so this is ignored.
+        LIST.push_back(&NODES[0]);
+
+        let mut popped = 0;
+        while let Some(node) = LIST.pop_front() {
+            assert!(NODES.iter().any(|n| core::ptr::eq(n, node)));
+            popped += 1;
+        }
+        assert_eq!(popped, THREADS);
+    }
+}